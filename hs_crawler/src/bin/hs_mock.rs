@@ -0,0 +1,223 @@
+//! Stand-in for hs-offenburg.de and swfr.de, serving the html in `fixtures/`.
+//!
+//! ```text
+//! cargo run --manifest-path hs_crawler/Cargo.toml --bin hs_mock -- --port 8080 [--slow MS] [--fail-every N] [--malformed]
+//! HS_CRAWLER_HS_URL=http://127.0.0.1:8080 HS_CRAWLER_SWFR_URL=http://127.0.0.1:8080 hs_app
+//! ```
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const COURSE_INDEX: &str = include_str!("../../fixtures/course_index.html");
+const TIMETABLE_WEEK_0: &str = include_str!("../../fixtures/timetable_week0.html");
+const TIMETABLE_WEEK_1: &str = include_str!("../../fixtures/timetable_week1.html");
+const CANTEEN_WEEK_0: &str = include_str!("../../fixtures/canteen_week0.html");
+const CANTEEN_WEEK_1: &str = include_str!("../../fixtures/canteen_week1.html");
+
+const USAGE: &str = "\
+Usage: hs_mock [OPTIONS]
+
+Options:
+    --port PORT       Port to listen on (default 8080).
+    --slow MS         Delay every response by MS milliseconds.
+    --fail-every N    Answer every N-th request with 500 (1 = always).
+    --malformed       Serve broken html the parsers can't read.
+";
+
+#[derive(Clone, Debug)]
+struct Options {
+    port: u16,
+    slow_ms: u64,
+    fail_every: usize,
+    malformed: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        port: 8080,
+        slow_ms: 0,
+        fail_every: 0,
+        malformed: false,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}.", name))
+        };
+
+        match &*arg {
+            "--port" => opts.port = value("--port")?.parse().map_err(|_| "Invalid port.")?,
+            "--slow" => opts.slow_ms = value("--slow")?.parse().map_err(|_| "Invalid delay.")?,
+            "--fail-every" => {
+                opts.fail_every = value("--fail-every")?.parse().map_err(|_| "Invalid count.")?
+            }
+            "--malformed" => opts.malformed = true,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            other => return Err(format!("Unknown argument '{}'.\n\n{}", other, USAGE)),
+        }
+    }
+
+    Ok(opts)
+}
+
+fn main() {
+    let opts = match parse_args() {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    let listener = match TcpListener::bind(("127.0.0.1", opts.port)) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Unable to listen on port {}: {}", opts.port, e);
+            process::exit(1);
+        }
+    };
+
+    let base = format!("http://127.0.0.1:{}", opts.port);
+    eprintln!("Serving fixtures on {} ({:?})", base, opts);
+    eprintln!("  export HS_CRAWLER_HS_URL={}", base);
+    eprintln!("  export HS_CRAWLER_SWFR_URL={}", base);
+
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming().flatten() {
+        let opts = opts.clone();
+        let base = base.clone();
+        let counter = counter.clone();
+
+        thread::spawn(move || {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            if let Err(e) = handle(stream, &opts, &base, n) {
+                eprintln!("#{} failed: {}", n, e);
+            }
+        });
+    }
+}
+
+fn handle(mut stream: TcpStream, opts: &Options, base: &str, n: usize) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip headers, we don't care.
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+    if opts.slow_ms > 0 {
+        thread::sleep(Duration::from_millis(opts.slow_ms));
+    }
+
+    let (status, body) = if opts.fail_every > 0 && n % opts.fail_every == 0 {
+        (500, "<h1>500 Internal Server Error</h1>".to_string())
+    } else {
+        match route(&target) {
+            Some(page) => {
+                let page = page.replace("{{HS}}", base).replace("{{SWFR}}", base);
+                if opts.malformed {
+                    (200, malform(&page))
+                } else {
+                    (200, page)
+                }
+            }
+            None => (404, "<h1>404 Not Found</h1>".to_string()),
+        }
+    };
+
+    eprintln!("#{} {} -> {}", n, target, status);
+
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn route(target: &str) -> Option<&'static str> {
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, ""),
+    };
+
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|kv| {
+                let mut parts = kv.splitn(2, '=');
+                Some((parts.next()?, parts.next().unwrap_or("")))
+            })
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.to_string())
+    };
+
+    match path {
+        "/studium/vorlesungsplaene/" | "/studium/vorlesungsplaene" => Some(COURSE_INDEX),
+        "/index.php" if param("id").as_deref() == Some("6627") => {
+            match param("week").and_then(|w| w.parse::<usize>().ok()).unwrap_or(0) % 2 {
+                0 => Some(TIMETABLE_WEEK_0),
+                _ => Some(TIMETABLE_WEEK_1),
+            }
+        }
+        "/essen-trinken/speiseplaene/mensa-offenburg/" => {
+            let next_week = param("tx_swfrspeiseplan_pi1[weekToShow]")
+                .or_else(|| param("tx_swfrspeiseplan_pi1%5BweekToShow%5D"))
+                .map(|w| w != "0")
+                .unwrap_or(false);
+
+            if next_week {
+                Some(CANTEEN_WEEK_1)
+            } else {
+                Some(CANTEEN_WEEK_0)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Renames the classes the crawlers look for and cuts the page short.
+fn malform(page: &str) -> String {
+    let broken = page
+        .replace("timetable", "timetabel")
+        .replace("lastcol", "last-col")
+        .replace("menu-tagesplan", "menu-tages-plan")
+        .replace("next-week", "nextweek")
+        .replace("index.php?id=6627", "index.php?id=6628");
+
+    let mut cut = broken.len() * 2 / 3;
+    while !broken.is_char_boundary(cut) {
+        cut -= 1;
+    }
+
+    broken[..cut].to_string()
+}
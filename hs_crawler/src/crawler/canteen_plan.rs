@@ -2,8 +2,9 @@ use super::*;
 
 use crate::util::*;
 
+use crate::report::*;
+
 use std::io;
-use std::io::Read;
 
 use std::collections::HashMap;
 
@@ -11,16 +12,97 @@ use select::document::Document;
 use select::predicate::*;
 
 use chrono::{Date, Local};
-use reqwest;
 
 type CanteenPlan = HashMap<Date<Local>, Vec<String>>;
 
-const URL_THIS_WEEK: &str = "https://www.swfr.de/essen-trinken/speiseplaene/mensa-offenburg/";
+/// A single menu entry of a day, split into its parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Meal {
+    /// Like "Essen 1" or "Buffet", if the plan names it.
+    pub name: Option<String>,
+    pub description: String,
+    /// Like "Erdnüsse (E)".
+    pub allergens: Vec<String>,
+    /// Like "vegan" or "Schwein".
+    pub labels: Vec<String>,
+    /// The entry as it was crawled.
+    pub text: String,
+}
+
+const PREFIX_ALLERGENS: &str = "enthält Allergene";
+const PREFIX_LABELS: &str = "Kennzeichnungen";
+
+impl Meal {
+    /// Parses an entry as returned by `get`. Returns `None` for empty entries.
+    pub fn parse(text: &str) -> Option<Meal> {
+        let mut allergens = vec![];
+        let mut labels = vec![];
+
+        let lines = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .filter(|l| {
+                if l.starts_with(PREFIX_ALLERGENS) {
+                    allergens.extend(list(l));
+                } else if l.starts_with(PREFIX_LABELS) {
+                    labels.extend(list(l));
+                } else {
+                    return true;
+                }
+                false
+            })
+            .collect::<Vec<&str>>();
+
+        let (name, description) = match &lines[..] {
+            [] => return None,
+            [single] => (None, single.to_string()),
+            [first, rest @ ..] => (Some(first.to_string()), rest.join(" ")),
+        };
+
+        Some(Meal {
+            name,
+            description,
+            allergens,
+            labels,
+            text: text.trim().to_string(),
+        })
+    }
+}
+
+/// The comma separated items after the colon of `line`.
+fn list(line: &str) -> Vec<String> {
+    let items = match line.find(':') {
+        Some(i) => &line[i + 1..],
+        None => return vec![],
+    };
+
+    items
+        .split(',')
+        .map(|i| i.trim())
+        .filter(|i| !i.is_empty())
+        .map(|i| i.to_string())
+        .collect()
+}
+
+/// All meals of one day of a `CanteenPlan`.
+pub fn meals(day: &[String]) -> Vec<Meal> {
+    day.iter().flat_map(|text| Meal::parse(text)).collect()
+}
+
+const PATH_CANTEENS: &str = "/essen-trinken/speiseplaene/";
 //const URL_NEXT_WEEK : &str = "https://www.swfr.de/essen-trinken/speiseplaene/mensa-offenburg/?tx_swfrspeiseplan_pi1[weekToShow]=1";
 
+fn url_this_week(canteen: &str) -> String {
+    format!("{}{}{}/", swfr_base_url(), PATH_CANTEENS, canteen.trim().trim_matches('/'))
+}
+
 use std::sync::mpsc::Receiver;
-pub fn get_async(q: Query) -> Receiver<Result<CanteenPlan, String>> {
-    dirty_err_async(move || get(q))
+/// `canteen` is the SWFR canteen as in its url, like `DEFAULT_CANTEEN`.
+pub fn get_async(q: Query, canteen: &str) -> Receiver<Result<CanteenPlan, String>> {
+    let canteen_copy = canteen.to_string();
+
+    dirty_err_async(move || get(q, &canteen_copy))
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -29,45 +111,48 @@ pub enum Query {
     NextWeek,
 }
 
-fn get_url_next_week() -> Result<String, DirtyError> {
-    let res = reqwest::blocking::get(URL_THIS_WEEK)?;
-
-    if res.status() != 200 {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Didn't get course table.").into());
-    }
-
-    let mut html = String::new();
-    res.take(MAX_RESPONSE_SIZE).read_to_string(&mut html)?;
+fn get_url_next_week(canteen: &str, report: &mut Report) -> Result<String, DirtyError> {
+    let html = fetch_html(&url_this_week(canteen), "canteen plan", report)?;
 
     let dom = Document::from(&*html);
 
-    let menu_url = dom.find(And(Class("next-week"), Class("text-right")))
-        .next().unwrap() // todo
-        .attr("href").unwrap()
-        .to_owned();
+    let menu_url = match dom.find(And(Class("next-week"), Class("text-right")))
+        .next()
+        .and_then(|link| link.attr("href")) {
+        Some(url) => url.to_owned(),
+        None => {
+            report.miss_structure("a.next-week.text-right");
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected next-week link in html").into());
+        }
+    };
 
+    if menu_url.starts_with("http://") || menu_url.starts_with("https://") {
+        return Ok(menu_url);
+    }
 
-    Ok(format!("https://www.swfr.de{}", menu_url))
+    Ok(format!("{}{}", swfr_base_url(), menu_url))
 }
 
+pub fn get(q: Query, canteen: &str) -> Result<CanteenPlan, DirtyError> {
+    get_with_report(q, canteen, &mut Report::default())
+}
 
-pub fn get(q: Query) -> Result<CanteenPlan, DirtyError> {
-    let res = match q {
-        Query::ThisWeek => reqwest::blocking::get(URL_THIS_WEEK)?,
-        Query::NextWeek => reqwest::blocking::get(&get_url_next_week()?)?,
-    };
-
-    if res.status() != 200 {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Didn't get course table.").into());
-    }
+/// Like `get`, but also returns what was fetched and which selectors missed.
+pub fn diagnose(q: Query, canteen: &str) -> (Result<CanteenPlan, String>, Report) {
+    let mut report = Report::default();
+    let res = get_with_report(q, canteen, &mut report).map_err(|e| e.to_string());
 
-    let mut html = String::new();
-    res.take(MAX_RESPONSE_SIZE).read_to_string(&mut html)?;
+    (res, report)
+}
 
-    // Strange workaround.
-    html = html.replace("<br>", "\n");
+fn get_with_report(q: Query, canteen: &str, report: &mut Report) -> Result<CanteenPlan, DirtyError> {
+    let url = match q {
+        Query::ThisWeek => url_this_week(canteen),
+        Query::NextWeek => get_url_next_week(canteen, report)?,
+    };
 
-    let dom = Document::from(&*html);
+    let html = fetch_html(&url, "canteen plan", report)?;
+    let menu_plan = parse_html(&html, report);
 
     let mut date = last_monday_or_next_monday_on_sundays();
     if q == Query::NextWeek {
@@ -76,6 +161,28 @@ pub fn get(q: Query) -> Result<CanteenPlan, DirtyError> {
         }
     }
 
+    let daily_menu_plan: CanteenPlan = menu_plan
+        .into_iter()
+        .map(|d| {
+            let ret = (date, d);
+            date = date.succ();
+            ret
+        }).collect();
+
+    Ok(daily_menu_plan)
+}
+
+/// The entries of each day of a plan page, from monday on.
+fn parse_html(html: &str, report: &mut Report) -> Vec<Vec<String>> {
+    // Strange workaround.
+    let html = html.replace("<br>", "\n");
+
+    let dom = Document::from(&*html);
+
+    if dom.find(Class("tab-content")).next().is_none() {
+        report.miss_structure(".tab-content");
+    }
+
     let menu_plan = dom
         .find(Class("tab-content"))
         .flat_map(|maybe_plan| {
@@ -88,20 +195,75 @@ pub fn get(q: Query) -> Result<CanteenPlan, DirtyError> {
                             .lines()
                             .map(|s| s.trim())
                             .filter(|s| !s.is_empty())
-                            .filter(|l| !l.starts_with("enthält Allergene"))
-                            .filter(|l| !l.starts_with("Kennzeichnungen"))
                             .fold(String::new(), |a, b| a + "\n" + &b)
                     }).collect::<Vec<String>>()
             })
         }).collect::<Vec<Vec<String>>>();
 
-    let daily_menu_plan: CanteenPlan = menu_plan
-        .into_iter()
-        .map(|d| {
-            let ret = (date.clone(), d);
-            date = date.succ();
-            ret
-        }).collect();
+    if menu_plan.is_empty() {
+        report.miss_structure(".tab-content .menu-tagesplan");
+    }
+    for (i, day) in menu_plan.iter().enumerate() {
+        if day.is_empty() {
+            report.miss(format!("day {}: .menu-info", i + 1));
+        }
+    }
 
-    Ok(daily_menu_plan)
+    menu_plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEEK_0: &str = include_str!("../../fixtures/canteen_week0.html");
+
+    fn fixture_meals() -> Vec<Vec<Meal>> {
+        parse_html(WEEK_0, &mut Report::default())
+            .iter()
+            .map(|day| meals(day))
+            .collect()
+    }
+
+    #[test]
+    fn parses_name_description_allergens_and_labels() {
+        let week = fixture_meals();
+
+        assert_eq!(week[0][0], Meal {
+            name: Some("Essen 1".to_string()),
+            description: "Schweineschnitzel mit Pommes frites und Salat".to_string(),
+            allergens: vec!["Gluten (A)".to_string(), "Eier (C)".to_string()],
+            labels: vec!["Schwein".to_string()],
+            text: "Essen 1\nSchweineschnitzel mit Pommes frites und Salat\n\
+                   enthält Allergene: Gluten (A), Eier (C)\nKennzeichnungen: Schwein".to_string(),
+        });
+    }
+
+    #[test]
+    fn every_fixture_entry_is_a_named_meal() {
+        let week = fixture_meals();
+        assert!(!week.is_empty());
+
+        for meal in week.iter().flatten() {
+            assert!(meal.name.is_some(), "{:?}", meal);
+            assert!(!meal.description.is_empty(), "{:?}", meal);
+            assert!(!meal.allergens.is_empty(), "{:?}", meal);
+            assert!(!meal.labels.is_empty(), "{:?}", meal);
+        }
+    }
+
+    #[test]
+    fn old_entries_without_allergen_lines() {
+        let meal = Meal::parse("Essen 2\nGemüsecurry mit Basmatireis").unwrap();
+
+        assert_eq!(meal.name.as_deref(), Some("Essen 2"));
+        assert_eq!(meal.description, "Gemüsecurry mit Basmatireis");
+        assert!(meal.allergens.is_empty());
+        assert!(meal.labels.is_empty());
+    }
+
+    #[test]
+    fn empty_entries_are_no_meal() {
+        assert_eq!(Meal::parse(" \n "), None);
+    }
 }
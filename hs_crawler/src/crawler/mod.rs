@@ -1,7 +1,47 @@
 pub mod canteen_plan;
+pub mod report;
 pub mod timetable;
 pub mod weather;
 
 pub const MAX_RESPONSE_SIZE: u64 = 10 * 1024 * 1024;
 pub const DEFAULT_TIMEOUT_SEC: u64 = 30;
 
+pub const HS_BASE_URL: &str = "https://www.hs-offenburg.de";
+pub const SWFR_BASE_URL: &str = "https://www.swfr.de";
+
+/// Overrides `HS_BASE_URL`, e.g. `http://127.0.0.1:8080` for the mock server.
+pub const HS_BASE_URL_ENV: &str = "HS_CRAWLER_HS_URL";
+/// Overrides `SWFR_BASE_URL`.
+pub const SWFR_BASE_URL_ENV: &str = "HS_CRAWLER_SWFR_URL";
+
+/// The SWFR canteen to crawl if the caller has no other, as in its url.
+pub const DEFAULT_CANTEEN: &str = "mensa-offenburg";
+
+/// Set to anything but "" or "0" and no request is made at all.
+pub const OFFLINE_ENV: &str = "HS_CRAWLER_OFFLINE";
+
+/// Base url of the HS website, without trailing slash.
+pub fn hs_base_url() -> String {
+    base_url_from_env(HS_BASE_URL_ENV, HS_BASE_URL)
+}
+
+/// Base url of the SWFR website, without trailing slash.
+pub fn swfr_base_url() -> String {
+    base_url_from_env(SWFR_BASE_URL_ENV, SWFR_BASE_URL)
+}
+
+/// Whether requests are forbidden, see `OFFLINE_ENV`.
+pub fn offline() -> bool {
+    match std::env::var(OFFLINE_ENV) {
+        Ok(ref v) => !v.trim().is_empty() && v.trim() != "0",
+        Err(_) => false,
+    }
+}
+
+fn base_url_from_env(var: &str, default: &str) -> String {
+    match std::env::var(var) {
+        Ok(ref url) if !url.trim().is_empty() => url.trim().trim_end_matches('/').to_string(),
+        _ => default.to_string(),
+    }
+}
+
@@ -0,0 +1,82 @@
+use super::*;
+
+use crate::util::*;
+
+use std::io;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use reqwest;
+
+/// One http request made while crawling.
+#[derive(Clone, Debug)]
+pub struct FetchInfo {
+    pub url: String,
+    /// `None` if there was no response at all.
+    pub status: Option<u16>,
+    pub elapsed: Duration,
+    pub bytes: usize,
+}
+
+/// Everything a crawl did besides returning data, see `timetable::diagnose`.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub fetches: Vec<FetchInfo>,
+    /// Selectors that matched nothing although they should have.
+    pub misses: Vec<String>,
+    /// Like `misses`, but the page lacks a part the parser can't do without.
+    pub structure_misses: Vec<String>,
+}
+
+impl Report {
+    pub(crate) fn miss<S: Into<String>>(&mut self, what: S) {
+        self.misses.push(what.into());
+    }
+
+    pub(crate) fn miss_structure<S: Into<String>>(&mut self, what: S) {
+        self.structure_misses.push(what.into());
+    }
+}
+
+/// GETs `url` and returns the body if the status was 200.
+/// `what` ends up in the error message, e.g. "course table".
+pub(crate) fn fetch_html(url: &str, what: &str, report: &mut Report) -> Result<String, DirtyError> {
+    if offline() {
+        return Err(io::Error::other("Offline mode.").into());
+    }
+
+    let start = Instant::now();
+
+    let res = match reqwest::blocking::get(url) {
+        Ok(r) => r,
+        Err(e) => {
+            report.fetches.push(FetchInfo {
+                url: url.to_string(),
+                status: None,
+                elapsed: start.elapsed(),
+                bytes: 0,
+            });
+            return Err(e.into());
+        }
+    };
+
+    let status = res.status();
+
+    let mut html = String::new();
+    let read = res.take(MAX_RESPONSE_SIZE).read_to_string(&mut html);
+
+    report.fetches.push(FetchInfo {
+        url: url.to_string(),
+        status: Some(status.as_u16()),
+        elapsed: start.elapsed(),
+        bytes: html.len(),
+    });
+
+    if status != 200 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Didn't get {}.", what)).into());
+    }
+
+    read?;
+
+    Ok(html)
+}
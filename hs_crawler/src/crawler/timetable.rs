@@ -2,21 +2,107 @@ use super::*;
 
 use crate::util::*;
 
+use crate::report::*;
+
 use std::io;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::io::Read;
 
 use std::collections::HashMap;
 
 use select::document::Document;
 use select::predicate::*;
 
-use chrono::{Date, Local};
-use reqwest;
+use chrono::{Date, Local, NaiveTime};
 
 type Timetable = HashMap<Date<Local>, Vec<String>>;
 
+/// Start and end (hour, minute) of the lecture slots, one per timetable row.
+pub const SLOT_TIMES: [((u32, u32), (u32, u32)); 7] = [
+    ((8, 0), (9, 30)),
+    ((9, 45), (11, 15)),
+    ((11, 30), (13, 0)),
+    ((14, 0), (15, 30)),
+    ((15, 45), (17, 15)),
+    ((17, 30), (19, 0)),
+    ((19, 15), (20, 45)),
+];
+
+/// Start and end time of the `slot`-th row of a day, starting at 0.
+pub fn slot_time(slot: usize) -> Option<(NaiveTime, NaiveTime)> {
+    let ((sh, sm), (eh, em)) = SLOT_TIMES.get(slot)?;
+
+    Some((NaiveTime::from_hms(*sh, *sm, 0), NaiveTime::from_hms(*eh, *em, 0)))
+}
+
+/// A single non-empty timetable cell, split into its parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lecture {
+    pub course: String,
+    pub slot: usize,
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+    pub title: String,
+    pub lecturer: Option<String>,
+    pub room: Option<String>,
+    /// The cell as it was crawled.
+    pub text: String,
+}
+
+impl Lecture {
+    /// Parses a cell as returned by `get`. Returns `None` for empty slots.
+    pub fn parse(course: &str, slot: usize, text: &str) -> Option<Lecture> {
+        let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+
+        let title = lines.next()?.to_string();
+
+        let mut lecturer = None;
+        let mut room = None;
+        for line in lines {
+            if room.is_none() && looks_like_room(line) {
+                room = Some(line.to_string());
+            } else if lecturer.is_none() {
+                lecturer = Some(line.to_string());
+            }
+        }
+
+        let times = slot_time(slot);
+
+        Some(Lecture {
+            course: course.to_string(),
+            slot,
+            start: times.map(|t| t.0),
+            end: times.map(|t| t.1),
+            title,
+            lecturer,
+            room,
+            text: text.trim().to_string(),
+        })
+    }
+}
+
+/// All lectures of one day of a `Timetable`, in slot order.
+pub fn lectures(course: &str, day: &[String]) -> Vec<Lecture> {
+    day.iter()
+        .enumerate()
+        .flat_map(|(slot, text)| Lecture::parse(course, slot, text))
+        .collect()
+}
+
+/// Rooms look like "B104", "A 204" or "Raum D013".
+fn looks_like_room(line: &str) -> bool {
+    let line = line.trim_start_matches("Raum").trim();
+    let mut chars = line.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_uppercase() => (),
+        _ => return false,
+    }
+
+    let rest = chars.as_str().trim_start();
+    !rest.is_empty()
+        && rest.chars().next().map(|c| c.is_ascii_digit()).unwrap_or(false)
+        && rest.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
 use std::sync::mpsc::Receiver;
 pub fn get_async(q: Query, course: &str) -> Receiver<Result<Timetable, String>> {
     let course_copy = course.to_string();
@@ -24,14 +110,56 @@ pub fn get_async(q: Query, course: &str) -> Receiver<Result<Timetable, String>>
     dirty_err_async(move || get(q, &course_copy))
 }
 
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum Query {
     ThisWeek,
     NextWeek,
 }
 
 pub fn get(q: Query, course: &str) -> Result<Timetable, DirtyError> {
-    let index = download_timetable_index()?;
+    get_with_report(q, course, &mut Report::default())
+}
 
+/// Like `get` with an `index` from `diagnose_index`, but also returns what
+/// was fetched and which selectors missed.
+pub fn diagnose(q: Query, course: &str, index: &LowercaseCourseToUrl) -> (Result<Timetable, String>, Report) {
+    let mut report = Report::default();
+    let res = get_from_index(q, course, index, &mut report).map_err(|e| e.to_string());
+
+    (res, report)
+}
+
+/// Downloads the course index, see `diagnose`.
+pub fn diagnose_index() -> (Result<LowercaseCourseToUrl, String>, Report) {
+    let mut report = Report::default();
+    let res = download_timetable_index(&mut report).map_err(|e| e.to_string());
+
+    (res, report)
+}
+
+/// Names of all courses that have a timetable, uppercase and sorted.
+/// This call is blocking.
+pub fn courses() -> Result<Vec<String>, DirtyError> {
+    let mut courses = download_timetable_index(&mut Report::default())?
+        .keys()
+        .map(|c| c.to_uppercase())
+        .collect::<Vec<String>>();
+    courses.sort();
+
+    Ok(courses)
+}
+
+fn get_with_report(q: Query, course: &str, report: &mut Report) -> Result<Timetable, DirtyError> {
+    let index = download_timetable_index(report)?;
+    get_from_index(q, course, &index, report)
+}
+
+fn get_from_index(
+    q: Query,
+    course: &str,
+    index: &LowercaseCourseToUrl,
+    report: &mut Report,
+) -> Result<Timetable, DirtyError> {
     let course_url = index
         .get(&course.to_lowercase())
         .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Course not found."))?;
@@ -39,14 +167,14 @@ pub fn get(q: Query, course: &str) -> Result<Timetable, DirtyError> {
     match q {
         Query::ThisWeek => {
             let date = last_monday();
-            download_timetable_from_url(&date, course_url)
+            download_timetable_from_url(&date, course_url, report)
         }
         Query::NextWeek => {
             let mut date = last_monday();
             for _ in 0..7 {
                 date = date.succ();
             }
-            download_timetable_from_url(&date, &course_url.replace("week=0", "week=1"))
+            download_timetable_from_url(&date, &course_url.replace("week=0", "week=1"), report)
         }
     }
 }
@@ -56,38 +184,47 @@ pub fn get(q: Query, course: &str) -> Result<Timetable, DirtyError> {
 fn download_timetable_from_url(
     start_date: &Date<Local>,
     url: &str,
+    report: &mut Report,
 ) -> Result<Timetable, DirtyError> {
-    let mut date = start_date.clone();
-
-    let res = reqwest::blocking::get(url)?;
-
-    if res.status() != 200 {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Didn't get course table.").into());
-    }
+    let mut date = *start_date;
 
-    let mut html = String::new();
-    res.take(MAX_RESPONSE_SIZE).read_to_string(&mut html)?;
+    let html = fetch_html(url, "course table", report)?;
 
     let dom = Document::from(&*html);
 
-    let timetable_node = dom.find(Class("timetable")).next().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Expected timetable class in html",
-        )
-    })?;
+    let timetable_node = match dom.find(Class("timetable")).next() {
+        Some(node) => node,
+        None => {
+            report.miss_structure(".timetable");
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Expected timetable class in html",
+            ).into());
+        }
+    };
 
-    let timetable: Timetable = timetable_node
+    let rows = timetable_node
         .find(Attr("scope", "row"))
         .map(|row| {
             row.find((Class("lastcol")).and(Name("td")))
                 .map(|column| column.text().ihh_fix())
                 .collect::<Vec<String>>()
-        }).collect::<Vec<Vec<String>>>()
+        }).collect::<Vec<Vec<String>>>();
+
+    if rows.is_empty() {
+        report.miss_structure(".timetable [scope=row]");
+    }
+    for (i, row) in rows.iter().enumerate() {
+        if row.is_empty() {
+            report.miss(format!("row {}: td.lastcol", i + 1));
+        }
+    }
+
+    let timetable: Timetable = rows
         .transpose()
         .into_iter()
         .map(|d| {
-            let ret = (date.clone(), d);
+            let ret = (date, d);
             date = date.succ();
             ret
         }).collect();
@@ -96,33 +233,30 @@ fn download_timetable_from_url(
 }
 
 /// Data is stored as (lowercase_course_name : String, url : String).
-type LowercaseCourseToUrl = HashMap<String, String>;
+pub type LowercaseCourseToUrl = HashMap<String, String>;
 
-pub const TIMETABLE_INDEX: &str = "https://www.hs-offenburg.de/studium/vorlesungsplaene/";
+pub const TIMETABLE_INDEX: &str = "/studium/vorlesungsplaene/";
 /// Downloads all the links for the timetable of each course.
-/// `TIMETABLE_INDEX` is used as source.
+/// `TIMETABLE_INDEX` on `hs_base_url()` is used as source.
 /// This call is blocking.
-fn download_timetable_index() -> Result<LowercaseCourseToUrl, DirtyError> {
+fn download_timetable_index(report: &mut Report) -> Result<LowercaseCourseToUrl, DirtyError> {
     // Some constants for Parsing.
-    const LINK_FILTER_A: &str = "<a href=\"http://www.hs-offenburg.de/index.php?id=6627";
-    const LINK_FILTER_B: &str = "<a href=\"https://www.hs-offenburg.de/index.php?id=6627";
     const LINK_START: &str = "<a href=\"";
     const LINK_MIDDLE: &str = "\">";
     const LINK_END: &str = "</a>";
 
-    let res = reqwest::blocking::get(TIMETABLE_INDEX)?;
-    if res.status() != 200 {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Didn't get course index.").into());
-    }
+    let base = hs_base_url();
+    let host = base.trim_start_matches("https://").trim_start_matches("http://");
+    let link_filter_a = format!("<a href=\"http://{}/index.php?id=6627", host);
+    let link_filter_b = format!("<a href=\"https://{}/index.php?id=6627", host);
+    let upgrade_to_https = base.starts_with("https://");
 
-    // we need this to iterate over lines.
-    let reader = BufReader::new(res.take(MAX_RESPONSE_SIZE));
+    let html = fetch_html(&format!("{}{}", base, TIMETABLE_INDEX), "course index", report)?;
 
     // Does MAGIC #oldschool, don't ask.   // TODO: use select;
-    let course_to_url: HashMap<String, String> = reader
+    let course_to_url: HashMap<String, String> = html
         .lines()
-        .flat_map(|line| line)
-        .filter(|line| line.starts_with(LINK_FILTER_A) || line.starts_with(LINK_FILTER_B))
+        .filter(|line| line.starts_with(&link_filter_a) || line.starts_with(&link_filter_b))
         .flat_map(|line| {
             let parts = line
                 .replace(LINK_START, "")
@@ -132,12 +266,16 @@ fn download_timetable_index() -> Result<LowercaseCourseToUrl, DirtyError> {
                 .collect::<Vec<String>>();
 
             match &parts[..] {
-                [link, name] => Some((name.to_lowercase(), link.replace("http://", "https://"))),
+                [link, name] if upgrade_to_https => {
+                    Some((name.to_lowercase(), link.replace("http://", "https://")))
+                }
+                [link, name] => Some((name.to_lowercase(), link.to_string())),
                 _ => None,
             }
         }).collect();
 
     if course_to_url.is_empty() {
+        report.miss_structure(format!("{}/index.php?id=6627 links", host));
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             "Failed to parse HTML for course index",
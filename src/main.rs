@@ -17,10 +17,19 @@ extern crate dirs;
 
 extern crate clap;
 
+use hs_crawler::timetable::Lecture;
+
 mod ui;
 use crate::ui::theme::*;
 
 use crate::ui::cache;
+use crate::ui::changes::{self, Change, ChangeKind};
+use crate::ui::config::Config;
+use crate::ui::fetch::{FetchTimes, Selection};
+use crate::ui::filter::Filter;
+use crate::ui::merge::Timetables;
+use crate::ui::sanity;
+use crate::ui::session::Session;
 
 mod tui;
 use crate::tui::keys::Key;
@@ -32,38 +41,70 @@ use chrono::prelude::*;
 
 use std::thread;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 use std::sync::mpsc;
 
-use clap::{Arg, App};
+use clap::{Arg, App, SubCommand};
 
 const DEFAULT_SIZE: (isize, isize) = (80, 40);
 
+#[derive(Default)]
 pub struct AppData {
     pub canteen: HashMap<Date<Local>, Vec<String>>,
     pub timetable: HashMap<Date<Local>, Vec<String>>,
+    pub canteen_fetched: FetchTimes,
+    pub timetable_fetched: FetchTimes,
 }
 
 pub struct AppState {
-    course: String,
+    courses: Vec<String>,
+    config: Config,
+    /// Where hidden lectures are saved.
+    config_path: PathBuf,
 
     theme: Theme,
     day: Date<Local>,
 
-    data : AppData,
+    canteen: HashMap<Date<Local>, Vec<String>>,
+    /// Per course, like in the cache.
+    timetables: Timetables,
+
+    canteen_fetched: FetchTimes,
+    timetables_fetched: BTreeMap<String, FetchTimes>,
+    /// Weeks fetched before count as cached.
+    started: DateTime<Local>,
 
+    /// Fetches still running, for the timetables and the canteen plan.
     loading: (usize, usize),
 
     errors: Vec<String>,
+    /// Shown next to the data, unlike `errors` they don't block anything.
+    warnings: Vec<String>,
 
     display_mode: usize,
+
+    /// Index into the visible lectures of `day`.
+    selected: usize,
+    /// Of the whole view, clamped by the last render.
+    scroll: tui::ScrollPos,
+    /// Rules added with the hide key, newest last.
+    hidden: Vec<String>,
+
+    /// Timetable changes, highlighted until acknowledged.
+    changes: Vec<Change>,
 }
 
 pub enum Message {
     CanteenData(HashMap<Date<Local>, Vec<String>>),
-    TimetableData(HashMap<Date<Local>, Vec<String>>),
+    TimetableData(String, HashMap<Date<Local>, Vec<String>>),
+    /// A crawler failed, for the timetable of a course or the canteen plan.
+    FetchError(Option<String>, String),
     Error(String),
+    Warning(String),
+    /// Time to fetch everything again, see `refresh_minutes`.
+    Refresh,
     Key(Key),
     Resize(isize, isize),
 }
@@ -78,8 +119,11 @@ fn main() -> Result<(), String> {
             .short("c")
             .long("course")
             .takes_value(true)
-            .default_value("INFM2")
-            .help("Sets course to fetch timetable from.")
+            .multiple(true)
+            .number_of_values(1)
+            .use_delimiter(true)
+            .global(true)
+            .help("Sets courses to fetch timetables from, like AI3,INFM2. INFM2 if the config has none.")
         ).arg(
             Arg::with_name("simplecolor")
                 .short("s")
@@ -90,28 +134,79 @@ fn main() -> Result<(), String> {
                 .short("j")
                 .long("json")
                 .help("Dump data as JSON and exit.")
-        ).get_matches();
-
-    let course = matches.value_of("course").unwrap().to_uppercase();
+        ).arg(Arg::with_name("offline")
+                .long("offline")
+                .global(true)
+                .help("Never touch the network, only show what is cached.")
+        ).arg(ui::config::arg())
+        .args(&Selection::args())
+        .subcommand(SubCommand::with_name("doctor")
+            .about("Checks every data source and reports parse health.")
+        ).subcommand(ui::export::subcommand())
+        .subcommand(ui::serve::subcommand())
+        .subcommand(ui::status::subcommand())
+        .subcommand(ui::remind::subcommand())
+        .subcommand(ui::api::subcommand())
+        .subcommand(ui::config::subcommand())
+        .subcommand(ui::history::subcommand())
+        .subcommand(ui::changes::subcommand())
+        .subcommands(ui::plain::subcommands())
+        .get_matches();
+
+    let (config, config_path) = ui::config::from_matches(&matches)?;
+
+    ui::config::set_canteen(&config.canteen);
+
+    ui::history::set_enabled(config.history);
+
+    // Also reaches the processes `status --refresh` starts.
+    if matches.is_present("offline") {
+        std::env::set_var(hs_crawler::OFFLINE_ENV, "1");
+    }
 
-    if matches.is_present("simplecolor") {
-        use std::env;
-        env::set_var("COLORTERM", "");
+    match matches.subcommand() {
+        ("doctor", Some(_)) => return ui::doctor::run(&config),
+        ("export", Some(sub)) => return ui::export::run(&config, sub),
+        ("config", Some(_)) => return ui::config::print(&config, &config_path),
+        ("serve", Some(sub)) => return ui::serve::run(&config, sub),
+        ("status", Some(sub)) => return ui::status::run(&config, sub),
+        ("remind", Some(sub)) => return ui::remind::run(&config, sub),
+        ("api", Some(sub)) => return ui::api::run(&config, sub),
+        ("history", Some(sub)) => return ui::history::run(&config, sub),
+        ("changes", Some(sub)) => return ui::changes::run(&config, sub),
+        (name @ "today", Some(sub)) | (name @ "day", Some(sub)) | (name @ "week", Some(sub)) => {
+            return ui::plain::run(&config, name, sub)
+        }
+        _ => (),
     }
 
     if matches.is_present("json") {
-        return Ok(ui::json::print_as_json(&course));
+        return ui::json::print_as_json(&config, &Selection::from_matches(&matches));
     }
 
-    return ui_app(&course);
+    // Piped or redirected, a TUI makes no sense.
+    if !ui::plain::stdout_is_tty() {
+        return ui::plain::run(&config, "today", &matches);
+    }
+
+    let session = ui::session::load();
+    let mut config = config;
+    session.restore_sources(
+        &mut config,
+        matches.is_present("course") || ui::config::is_set(&config_path, "courses"),
+        ui::config::canteen_from_env().is_some() || ui::config::is_set(&config_path, "canteen"),
+    );
+    ui::config::set_canteen(&config.canteen);
+
+    ui_app(&config, &config_path, session)
 }
 
-fn ui_app(course : &str) -> Result<(), String> {
+fn ui_app(config : &Config, config_path : &Path, session : Session) -> Result<(), String> {
     use std::fmt::Write;
     let mut log = String::new();
 
 
-    tui::termutil::term_setup();
+    let guard = tui::termutil::TermGuard::new();
 
     let (outgoing, incoming) = mpsc::sync_channel::<Message>(256);
 
@@ -121,62 +216,88 @@ fn ui_app(course : &str) -> Result<(), String> {
     tui::termutil::register_for_resize(sighandler::sig_resize);
 
     let mut state = AppState {
-        course: course.to_string(),
+        courses: config.courses.clone(),
+        config: config.clone(),
+        config_path: config_path.to_path_buf(),
 
-        theme: select_colorscheme(),
-        day: {
-            let mut today = chrono::Local::today();
+        theme: colorscheme_by_name(&config.theme),
+        day: session.day(config).unwrap_or_else(|| initial_day(config)),
 
-            if chrono::Local::now().hour() > 18 {
-                today = today.succ();
-            }
+        canteen: Default::default(),
+        timetables: Default::default(),
 
-            if today.weekday() == chrono::Weekday::Sat {
-                today = today.succ();
-            }
-
-            if today.weekday() == chrono::Weekday::Sun {
-                today = today.succ();
-            }
-
-            today
-        },
-
-        data : AppData {
-            canteen: Default::default(),
-            timetable: Default::default(),
-        },
+        canteen_fetched: Default::default(),
+        timetables_fetched: Default::default(),
+        started: Local::now(),
 
         loading: (0, 0),
 
         errors: vec![],
+        warnings: vec![],
 
-        display_mode: 0,
+        display_mode: session.display_mode % 3,
+
+        selected: 0,
+        scroll: Default::default(),
+        // Only those still in the config, it might have been edited since.
+        hidden: session.hidden
+            .iter()
+            .filter(|r| config.filter.exclude.contains(r))
+            .cloned()
+            .collect(),
+
+        changes: changes::load(),
     };
 
-    match cache::read_cache(course) {
-        Ok(Some(data)) => state.data = data,
-        Ok(None) => (),
-        Err(e) => writeln!(log, "Error reading cache: {}", e).unwrap(),
+    for course in config.courses.iter() {
+        match cache::read_cache(course) {
+            Ok(Some(data)) => {
+                state.canteen.extend(data.canteen);
+                state.canteen_fetched.extend(data.canteen_fetched);
+                state.timetables.insert(course.clone(), data.timetable);
+                state.timetables_fetched.insert(course.clone(), data.timetable_fetched);
+            }
+            Ok(None) => (),
+            Err(e) => writeln!(log, "Error reading cache: {}", e).unwrap(),
+        }
     }
 
+    // None of the courses is cached yet, the canteen plan might be.
+    if state.canteen.is_empty() {
+        if let Ok(Some(data)) = cache::read_canteen_cache() {
+            state.canteen = data.canteen;
+            state.canteen_fetched = data.canteen_fetched;
+        }
+    }
 
-    setup_datasources(&state, &outgoing);
+
+    if session.day(config).is_some() {
+        state.selected = session.selected;
+        select(&mut state, 0);
+
+        // Clamped by the first render.
+        state.scroll.x = session.scroll.0;
+        state.scroll.y = session.scroll.1;
+    }
+
+    refresh(&mut state, &outgoing);
     setup_keyboard_datasource(&outgoing);
+    setup_refresh_timer(config.refresh_minutes, &outgoing);
 
     let mut size: (isize, isize) = tui::termutil::terminal_size().unwrap_or(DEFAULT_SIZE);
+    let mut screen = tui::VScreen::new();
 
     loop {
         // render;
 
         if ! state.errors.is_empty() {
-            render_errors(size.clone(), &state);
+            render_errors(&mut screen, size, &state);
         } else if state.display_mode % 3 == 0 {
-            render(size.clone(), &state);
+            state.scroll = render(&mut screen, size, &state);
         } else if state.display_mode % 3 == 2 {
-            table_render(size.clone(), &state, &state.data.timetable);
+            state.scroll = table_render(&mut screen, size, &state, timetable_slots);
         } else {
-            table_render(size.clone(), &state, &state.data.canteen);
+            state.scroll = table_render(&mut screen, size, &state, canteen_slots);
         };
 
         // process
@@ -191,20 +312,46 @@ fn ui_app(course : &str) -> Result<(), String> {
 
         match msg {
             Message::Key(key) => {
+                let keys = &state.config.keys;
+
                 match key {
-                    Key::Char('m') | Key::Char('M') => state.display_mode += 1,
+                    Key::Char(c) if keys.mode.contains(c) => {
+                        state.display_mode = (state.display_mode + 1) % 3;
+                        state.scroll = Default::default();
+                    }
+
+                    Key::Right => change_day(&mut state, 1),
+                    Key::Char(c) if keys.next_day.contains(c) => change_day(&mut state, 1),
 
-                    Key::Right | Key::Char('l') | Key::Char('L') => state.day = state.day.succ(),
+                    Key::Left => change_day(&mut state, -1),
+                    Key::Char(c) if keys.prev_day.contains(c) => change_day(&mut state, -1),
 
-                    Key::Left | Key::Char('h') | Key::Char('H') => state.day = state.day.pred(),
+                    Key::Down => state.scroll.lines(1),
+                    Key::Up => state.scroll.lines(-1),
+                    Key::PageDown => state.scroll.pages(1),
+                    Key::PageUp => state.scroll.pages(-1),
 
-                    Key::Ctrl('L') => size = tui::termutil::terminal_size().unwrap_or(DEFAULT_SIZE),
+                    Key::Char(c) if keys.select_next.contains(c) => select(&mut state, 1),
+                    Key::Char(c) if keys.select_prev.contains(c) => select(&mut state, -1),
 
-                    Key::Ctrl(_) | Key::ESC | Key::Char('q') | Key::Char('Q') => break,
+                    Key::Char(c) if keys.hide.contains(c) => hide_selected(&mut state),
+                    Key::Char(c) if keys.unhide.contains(c) => unhide_last(&mut state),
+
+                    Key::Ctrl('L') => {
+                        size = tui::termutil::terminal_size().unwrap_or(DEFAULT_SIZE);
+                        screen.invalidate();
+                    }
+
+                    Key::Ctrl(_) | Key::ESC => break,
+                    Key::Char(c) if keys.quit.contains(c) => break,
 
                     Key::Enter => {
                         let _deleted = state.errors.pop();
                     },
+
+                    Key::Char(c) if keys.hide_warnings.contains(c) => state.warnings.clear(),
+                    Key::Char(c) if keys.ack_changes.contains(c) => ack_changes(&mut state),
+                    Key::Char(c) if keys.refresh.contains(c) => refresh(&mut state, &outgoing),
                     _ => (),
                 }
             }
@@ -214,12 +361,70 @@ fn ui_app(course : &str) -> Result<(), String> {
                 }
                 handle_error(&mut state, e)
             },
+            Message::Warning(w) => {
+                handle_warning(&mut state, w);
+            }
+            Message::Refresh => refresh(&mut state, &outgoing),
+            Message::FetchError(course, e) => {
+                done_loading(&mut state, course.is_some(), &mut log);
+
+                if log.len() < 8192 {
+                    writeln!(log, "Error: {}", e).unwrap();
+                }
+
+                // Cached data is still worth showing, it gets labelled.
+                let cached = match course {
+                    Some(ref course) => state.timetables.get(course).map(|t| !t.is_empty()).unwrap_or(false),
+                    None => !state.canteen.is_empty(),
+                };
+
+                if cached {
+                    handle_warning(&mut state, e);
+                } else {
+                    handle_error(&mut state, e);
+                }
+            }
 
             Message::CanteenData(data) => {
-                state.data.canteen.extend(data);
+                for w in sanity::check_canteen(&data, &state.canteen) {
+                    handle_warning(&mut state, w);
+                }
+                if let Some(day) = data.keys().next() {
+                    state.canteen_fetched.insert(ui::fetch::monday_of(day), Local::now());
+                }
+                if let Err(e) = ui::history::record(None, &data, Local::now()) {
+                    writeln!(log, "Error writing history: {}", e).unwrap();
+                }
+                state.canteen.extend(data);
+                done_loading(&mut state, false, &mut log);
             }
-            Message::TimetableData(data) => {
-                state.data.timetable.extend(data);
+            Message::TimetableData(course, data) => {
+                let timetable = state.timetables.entry(course.clone()).or_default();
+                let warnings = sanity::check_timetable(&data, timetable);
+                let detected = changes::detect(&course, &data, timetable);
+                if let Some(day) = data.keys().next() {
+                    state.timetables_fetched
+                        .entry(course.clone())
+                        .or_default()
+                        .insert(ui::fetch::monday_of(day), Local::now());
+                }
+                if let Err(e) = ui::history::record(Some(&course), &data, Local::now()) {
+                    writeln!(log, "Error writing history: {}", e).unwrap();
+                }
+                timetable.extend(data);
+
+                for w in warnings {
+                    handle_warning(&mut state, format!("{}: {}", course, w));
+                }
+
+                if !detected.is_empty() {
+                    match changes::record(detected) {
+                        Ok(changes) => state.changes = changes,
+                        Err(e) => handle_warning(&mut state, format!("Changes not saved: {}", e)),
+                    }
+                }
+
+                done_loading(&mut state, true, &mut log);
             }
 
             Message::Resize(w, h) => {
@@ -228,11 +433,22 @@ fn ui_app(course : &str) -> Result<(), String> {
         }
     }
 
-    tui::termutil::term_unsetup();
+    drop(guard);
+
+    write_caches(&state, &mut log);
+
+    let session = Session {
+        display_mode: state.display_mode % 3,
+        courses: state.courses.clone(),
+        canteen: state.config.canteen.clone(),
+        day: Some(state.day.naive_local()),
+        selected: state.selected,
+        scroll: (state.scroll.x, state.scroll.y),
+        hidden: state.hidden.clone(),
+    };
 
-    match cache::write_cache(&state.data, course) {
-        Ok(()) => (),
-        Err(e) => writeln!(log, "Error writing cache: {}", e).unwrap(),
+    if let Err(e) = ui::session::save(&session) {
+        writeln!(log, "Error writing session: {}", e).unwrap();
     }
 
     eprintln!("{}", log);
@@ -240,6 +456,25 @@ fn ui_app(course : &str) -> Result<(), String> {
     Ok(())
 }
 
+/// The day to show first: tomorrow after `rollover_hour`, monday on weekends.
+pub fn initial_day(config : &Config) -> Date<Local> {
+    let mut today = chrono::Local::today();
+
+    if chrono::Local::now().hour() > config.rollover_hour {
+        today = today.succ();
+    }
+
+    if config.skip_weekend && today.weekday() == chrono::Weekday::Sat {
+        today = today.succ();
+    }
+
+    if config.skip_weekend && today.weekday() == chrono::Weekday::Sun {
+        today = today.succ();
+    }
+
+    today
+}
+
 mod sighandler {
     use super::Message;
 
@@ -278,10 +513,134 @@ mod sighandler {
     }
 }
 
+/// When the oldest data shown for the week of `day` was fetched, if it came from the cache.
+fn cached_since(state: &AppState, day: &Date<Local>) -> Option<DateTime<Local>> {
+    let monday = ui::fetch::monday_of(day);
+
+    state.courses
+        .iter()
+        .flat_map(|c| state.timetables_fetched.get(c))
+        .chain(std::iter::once(&state.canteen_fetched))
+        .filter_map(|times| times.get(&monday))
+        .filter(|at| **at < state.started)
+        .min()
+        .cloned()
+}
+
+fn change_day(state: &mut AppState, days: i64) {
+    state.day = state.day + chrono::Duration::days(days);
+    state.selected = 0;
+    state.scroll = Default::default();
+}
+
+/// The lectures of the shown day that the filter lets through.
+fn visible_lectures(state: &AppState) -> Vec<Lecture> {
+    ui::merge::lectures(&state.timetables, &state.day)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|l| !state.config.filter.hides(l))
+        .collect()
+}
+
+fn select(state: &mut AppState, delta: isize) {
+    let count = visible_lectures(state).len() as isize;
+    state.selected = (state.selected as isize + delta).max(0).min((count - 1).max(0)) as usize;
+}
+
+fn hide_selected(state: &mut AppState) {
+    // Nothing is selected in the canteen week.
+    if state.display_mode % 3 == 1 {
+        return;
+    }
+
+    let rule = match visible_lectures(state).get(state.selected) {
+        Some(lecture) => Filter::rule_for(lecture),
+        None => return,
+    };
+
+    if !state.config.filter.exclude.contains(&rule) {
+        state.config.filter.exclude.push(rule.clone());
+        state.hidden.push(rule);
+        save_filter(state);
+    }
+
+    select(state, 0);
+}
+
+fn unhide_last(state: &mut AppState) {
+    if let Some(rule) = state.hidden.pop() {
+        state.config.filter.exclude.retain(|r| *r != rule);
+        save_filter(state);
+    }
+}
+
+fn ack_changes(state: &mut AppState) {
+    match changes::ack(&state.courses) {
+        Ok(changes) => state.changes = changes,
+        Err(e) => handle_warning(state, format!("Changes not saved: {}", e)),
+    }
+}
+
+fn save_filter(state: &mut AppState) {
+    if let Err(e) = ui::config::save_filter(&state.config_path, &state.config.filter) {
+        handle_warning(state, format!("Hidden lectures not saved: {}", e));
+    }
+}
+
+/// Fetches every source again, unless a fetch is still running or we are offline.
+/// New data is merged into what is shown, so nothing disappears meanwhile.
+fn refresh(state: &mut AppState, outgoing: &mpsc::SyncSender<Message>) {
+    if hs_crawler::offline() || state.loading != (0, 0) {
+        return;
+    }
+
+    setup_datasources(state, outgoing);
+}
+
+/// One fetch of the timetables (or the canteen plan) finished. Once all are
+/// done the cache is updated, so `status` and `remind` see the new data too.
+fn done_loading(state: &mut AppState, timetable: bool, log: &mut String) {
+    if timetable {
+        state.loading.0 = state.loading.0.saturating_sub(1);
+    } else {
+        state.loading.1 = state.loading.1.saturating_sub(1);
+    }
+
+    if state.loading == (0, 0) {
+        write_caches(state, log);
+    }
+}
+
+fn write_caches(state: &AppState, log: &mut String) {
+    use std::fmt::Write;
+
+    for course in state.courses.iter() {
+        let data = AppData {
+            canteen: state.canteen.clone(),
+            timetable: state.timetables.get(course).cloned().unwrap_or_default(),
+            canteen_fetched: state.canteen_fetched.clone(),
+            timetable_fetched: state.timetables_fetched.get(course).cloned().unwrap_or_default(),
+        };
+
+        match cache::write_cache(&data, course) {
+            Ok(()) => (),
+            Err(e) => if log.len() < 8192 {
+                writeln!(log, "Error writing cache: {}", e).unwrap()
+            },
+        }
+    }
+}
+
 fn handle_error(state: &mut AppState, err: String) {
     state.errors.push(err);
 }
 
+fn handle_warning(state: &mut AppState, warning: String) {
+    if !state.warnings.contains(&warning) {
+        state.warnings.push(warning);
+    }
+}
+
 fn setup_keyboard_datasource(outgoing: &mpsc::SyncSender<Message>) {
     let outgoing_cp = outgoing.clone();
 
@@ -299,56 +658,72 @@ fn setup_keyboard_datasource(outgoing: &mpsc::SyncSender<Message>) {
     });
 }
 
-fn setup_datasources(state: &AppState, outgoing: &mpsc::SyncSender<Message>) {
-    message_adapter(
-        hs_crawler::timetable::get_async(hs_crawler::timetable::Query::ThisWeek, &state.course),
-        &outgoing,
-        |r| match r {
-            Ok(content) => Message::TimetableData(content),
-            Err(s) => Message::Error(s),
-        },
-    );
+/// Sends a `Refresh` every `minutes`, never with 0.
+fn setup_refresh_timer(minutes: u64, outgoing: &mpsc::SyncSender<Message>) {
+    if minutes == 0 {
+        return;
+    }
 
-    message_adapter(
-        hs_crawler::timetable::get_async(hs_crawler::timetable::Query::NextWeek, &state.course),
-        &outgoing,
-        |r| match r {
-            Ok(content) => Message::TimetableData(content),
-            Err(s) => Message::Error(s),
-        },
-    );
+    let outgoing_cp = outgoing.clone();
+
+    thread::spawn(move || loop {
+        thread::sleep(std::time::Duration::from_secs(minutes * 60));
+
+        if outgoing_cp.send(Message::Refresh).is_err() {
+            return;
+        }
+    });
+}
+
+fn setup_datasources(state: &mut AppState, outgoing: &mpsc::SyncSender<Message>) {
+    // One message comes back for each of these, data or an error.
+    state.loading = (state.courses.len() * 2, 2);
+
+    for course in state.courses.iter() {
+        for query in [hs_crawler::timetable::Query::ThisWeek, hs_crawler::timetable::Query::NextWeek].iter() {
+            let course_copy = course.clone();
+
+            message_adapter(
+                hs_crawler::timetable::get_async(*query, course),
+                outgoing,
+                move |r| match r {
+                    Ok(content) => Message::TimetableData(course_copy.clone(), content),
+                    Err(s) => Message::FetchError(Some(course_copy.clone()), format!("{}: {}", course_copy, s)),
+                },
+            );
+        }
+    }
 
     message_adapter(
-        hs_crawler::canteen_plan::get_async(hs_crawler::canteen_plan::Query::ThisWeek),
-        &outgoing,
+        hs_crawler::canteen_plan::get_async(hs_crawler::canteen_plan::Query::ThisWeek, &state.config.canteen),
+        outgoing,
         |r| match r {
             Ok(content) => Message::CanteenData(content),
-            Err(s) => Message::Error(s),
+            Err(s) => Message::FetchError(None, s),
         },
     );
 
     message_adapter(
-        hs_crawler::canteen_plan::get_async(hs_crawler::canteen_plan::Query::NextWeek),
-        &outgoing,
+        hs_crawler::canteen_plan::get_async(hs_crawler::canteen_plan::Query::NextWeek, &state.config.canteen),
+        outgoing,
         |r| match r {
             Ok(content) => Message::CanteenData(content),
-            Err(s) => Message::Error(s),
+            Err(s) => Message::FetchError(None, s),
         },
     );
 }
 
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
-fn render(size: (isize, isize), state: &AppState) {
+/// Returns where the view is scrolled to, within the content.
+fn render(screen: &mut tui::VScreen, size: (isize, isize), state: &AppState) -> tui::ScrollPos {
     use crate::tui::*;
 
     let theme = &state.theme;
     let today = &state.day;
-    let canteen = &state.data.canteen;
-    let timetable = &state.data.timetable;
 
     let mut table_widget = GridV::new();
-    for (i,d) in timetable.get(&today).unwrap_or(&Default::default()).iter().enumerate() {
+    for (i,texts) in timetable_slots(state, today).unwrap_or_default().iter().enumerate() {
 
         let background = if i % 2 == 1 {
             theme.textback1
@@ -356,17 +731,23 @@ fn render(size: (isize, isize), state: &AppState) {
             theme.textback2
         };
 
-        table_widget.push(
-            VText::colored(theme.text, d)
-                .margin(1,0)
-                .centered()
-                .with_background(background)
-                .margin(1,0)
-        );
+        let mut row = GridH::new();
+        for (d, mark) in texts_or_blank(texts) {
+            let (fg, bg) = mark.colors(theme, background);
+
+            row.push(
+                VText::colored(fg, d)
+                    .margin(1,0)
+                    .centered()
+                    .with_background(bg)
+                    .margin(1,0)
+            );
+        }
+        table_widget.push(row);
     }
 
     let mut canteen_widget = GridV::new();
-    for (i,d) in canteen.get(&today).unwrap_or(&Default::default()).iter().enumerate() {
+    for (i,(d, mark)) in canteen_slots(state, today).unwrap_or_default().into_iter().flatten().enumerate() {
 
         let background = if i % 2 == 1 {
             theme.textback1
@@ -374,31 +755,62 @@ fn render(size: (isize, isize), state: &AppState) {
             theme.textback2
         };
 
+        let (fg, bg) = mark.colors(theme, background);
+
         canteen_widget.push(
-            VText::colored(theme.text, d)
+            VText::colored(fg, &d)
                 .margin(1,0)
                 .centered()
-                .with_background(background)
+                .with_background(bg)
                 .margin(1,0)
         );
     }
 
-    let loading = if canteen.len() == 0 || timetable.len() == 0 {
-        "\n\nLädt..."
+    let timetable_missing = state.courses
+        .iter()
+        .any(|c| state.timetables.get(c).map(|t| t.is_empty()).unwrap_or(true));
+
+    let loading = if hs_crawler::offline() {
+        "\n\nOffline"
+    } else if state.loading.0 > 0 && state.loading.1 > 0 {
+        "\n\nLädt Stundenplan und Mensa..."
+    } else if state.loading.0 > 0 {
+        "\n\nLädt Stundenplan..."
+    } else if state.loading.1 > 0 {
+        "\n\nLädt Mensa..."
+    } else if state.canteen.is_empty() || timetable_missing {
+        "\n\nKeine Daten"
     } else {
         ""
     };
 
+    let freshness = match cached_since(state, today) {
+        Some(at) => format!("\n\n{}", ui::fetch::cached_label(&at)),
+        None => String::new(),
+    };
+
+    let meals = state.canteen
+        .get(today)
+        .map(|items| hs_crawler::canteen_plan::meals(items))
+        .unwrap_or_default();
+
+    let best_pick = match state.config.diet.best_pick(&meals) {
+        Some(meal) => format!("\n\nTipp: {}", meal.description),
+        None => String::new(),
+    };
+
     let info_str = format!(
         "\
     Hochschul-App \n\tv{}\n\n\
-    {:10} {:02}.{:02}.{}{}
+    {:10} {:02}.{:02}.{}{}{}{}
     ",
         VERSION,
         german_weekday(today.weekday()),
         today.day(),
         today.month(),
         today.year(),
+        freshness,
+        best_pick,
         loading
     );
 
@@ -407,41 +819,188 @@ fn render(size: (isize, isize), state: &AppState) {
         .with_background(theme.textback1)
         .margin(1,1);
 
+    let keys = &state.config.keys;
+    let key = |binding: &str| ui::config::KeyBindings::label(binding);
+
     let help =
         VText::colored(
             theme.heading,
-            "\
+            &format!(
+                "\
     HELP
 
-    q => Quit
-    m => Modus
-    ▶ => Next
-    ◀ => Prev
+    {} => Quit
+    {} => Modus
+    ▶ {} => Next
+    ◀ {} => Prev
+    ▲▼ => Scroll
+    {}{} => Select
+    {} => Hide
+    {} => Unhide
+    {} => Refresh
     ",
+                key(&keys.quit),
+                key(&keys.mode),
+                key(&keys.next_day),
+                key(&keys.prev_day),
+                key(&keys.select_next),
+                key(&keys.select_prev),
+                key(&keys.hide),
+                key(&keys.unhide),
+                key(&keys.refresh),
+            ),
         ).margin(4,2);
 
+    let mut info_column = GridV::new().add(heading).add(help);
+
+    let changed = state.changes
+        .iter()
+        .filter(|c| !c.acked && state.courses.contains(&c.course))
+        .count();
+
+    if changed > 0 {
+        info_column.push(
+            VText::colored(
+                theme.error,
+                &format!("✎ {} timetable changes\n\n{} => Ack", changed, key(&keys.ack_changes)),
+            ).margin(1,0)
+        );
+    }
+
+    if !state.warnings.is_empty() {
+        let shown = state.warnings.iter().rev().take(3).cloned().collect::<Vec<String>>();
+
+        info_column.push(
+            VText::colored(
+                theme.error,
+                &format!("⚠ {}\n\n{} => Hide", shown.join("\n⚠ "), key(&keys.hide_warnings)),
+            ).margin(1,0)
+        );
+    }
+
+    // The info column only needs room for the help, the rest goes to the data.
     let grid_root = GridH::new()
-        .add(
-            GridV::new().add(heading).add(help).margin(2,1).centered(),
-        ).add(
-            table_widget.margin(2,1).centered()
+        .add_with(
+            info_column.margin(2,1).centered(),
+            Constraint::flex(1).min(30).max(40),
+        ).add_with(
+            table_widget.margin(2,1).centered(),
+            Constraint::flex(2),
         )
-        .add(
-            canteen_widget.margin(2,1).centered()
+        .add_with(
+            canteen_widget.margin(2,1).centered(),
+            Constraint::flex(2),
         );
 
-    let mut root = grid_root.centered().with_background(theme.background);
+    render_scrolled(screen, size, state, grid_root)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Plain,
+    /// The selected lecture or a preferred meal.
+    Highlight,
+    /// Not acknowledged yet, see `ui::changes`.
+    Changed,
+}
 
-    let (w, h) = size;
-    root.try_set_size(w, h);
-    root.render_to_stdout();
+impl Mark {
+    /// Foreground and background, `background` is that of the row.
+    fn colors(self, theme: &Theme, background: tui::Color) -> (tui::Color, tui::Color) {
+        match self {
+            Mark::Plain => (theme.text, background),
+            Mark::Highlight => (theme.textback1, theme.heading),
+            Mark::Changed => (theme.textback1, theme.error),
+        }
+    }
+}
+
+/// The texts of each slot of a day, more than one if courses overlap.
+type Slots = Vec<Vec<(String, Mark)>>;
+
+fn timetable_slots(state: &AppState, day: &Date<Local>) -> Option<Slots> {
+    let several = state.courses.len() > 1;
+    let filter = &state.config.filter;
+
+    let selected = if *day == state.day {
+        visible_lectures(state).get(state.selected).map(|l| (l.slot, l.course.clone()))
+    } else {
+        None
+    };
+
+    let change = |course: &str, slot: usize| {
+        state.changes
+            .iter()
+            .find(|c| !c.acked && c.course == course && c.slot == slot && c.date == day.naive_local())
+    };
+
+    ui::merge::cells(&state.timetables, day).map(|slots| {
+        slots
+            .into_iter()
+            .enumerate()
+            .map(|(slot, entries)| {
+                let mut texts = entries
+                    .into_iter()
+                    .filter(|(course, text)| !filter.hides_cell(course, slot, text))
+                    .map(|(course, text)| {
+                        let mark = if selected == Some((slot, course.to_string())) {
+                            Mark::Highlight
+                        } else if change(course, slot).is_some() {
+                            Mark::Changed
+                        } else {
+                            Mark::Plain
+                        };
+
+                        (ui::merge::label(several, course, text), mark)
+                    })
+                    .collect::<Vec<(String, Mark)>>();
+
+                // What was there before stays visible until acknowledged.
+                for course in state.courses.iter() {
+                    if let Some(c) = change(course, slot).filter(|c| c.kind == ChangeKind::Removed) {
+                        texts.push((ui::merge::label(several, course, &format!("✗ {}", c.old)), Mark::Changed));
+                    }
+                }
+
+                texts
+            })
+            .collect()
+    })
+}
+
+fn canteen_slots(state: &AppState, day: &Date<Local>) -> Option<Slots> {
+    let diet = &state.config.diet;
+
+    state.canteen.get(day).map(|items| {
+        diet.shown(hs_crawler::canteen_plan::meals(items))
+            .iter()
+            .map(|m| {
+                let text = match m.name {
+                    Some(ref name) => format!("{}{}\n{}", diet.mark(m), name, m.description),
+                    None => format!("{}{}", diet.mark(m), m.description),
+                };
+                let mark = if diet.is_preferred(m) { Mark::Highlight } else { Mark::Plain };
+                vec![(text, mark)]
+            })
+            .collect()
+    })
+}
+
+/// An empty slot still takes up a row.
+fn texts_or_blank(texts: &[(String, Mark)]) -> Vec<(&str, Mark)> {
+    if texts.is_empty() {
+        vec![("", Mark::Plain)]
+    } else {
+        texts.iter().map(|(t, mark)| (t.as_str(), *mark)).collect()
+    }
 }
 
 fn table_render(
+    screen: &mut tui::VScreen,
     size: (isize, isize),
     state: &AppState,
-    content: &HashMap<Date<Local>, Vec<String>>,
-) {
+    content: fn(&AppState, &Date<Local>) -> Option<Slots>,
+) -> tui::ScrollPos {
     use crate::tui::*;
 
     let theme = &state.theme;
@@ -460,36 +1019,60 @@ fn table_render(
         );
 
         let mut table_widget = GridV::new();
-        table_widget.push(
-            VText::colored(theme.heading, &info_str).centered()
+        table_widget.push_with(
+            VText::colored(theme.heading, &info_str).centered(),
+            Constraint::fixed(3),
         );
 
-        for d in content.get(&today).unwrap_or(&Default::default()) {
+        let slots = content(state, &today);
+
+        for texts in slots.iter().flatten() {
             let bg = if i % 2 == 1 {
                 theme.textback1
             } else {
                 theme.textback2
             };
 
-            table_widget.push(
-                VText::colored(theme.text, d).centered().with_background(bg)
-            );
+            let mut row = GridH::new();
+            for (d, mark) in texts_or_blank(texts) {
+                let (fg, bg) = mark.colors(theme, bg);
+                row.push(VText::colored(fg, d).centered().with_background(bg));
+            }
+            table_widget.push(row);
 
             i += 1;
         }
 
-        if !content.get(&today).is_none() {
+        if slots.is_some() {
             grid_root.push(table_widget);
         }
 
         today = today.succ();
     }
 
-    let mut root = grid_root.centered().with_background(theme.background);
+    render_scrolled(screen, size, state, grid_root)
+}
+
+/// Shows the part of `content` the view is scrolled to, centered if it fits.
+fn render_scrolled<W: tui::Widget>(
+    screen: &mut tui::VScreen,
+    size: (isize, isize),
+    state: &AppState,
+    content: W,
+) -> tui::ScrollPos {
+    use crate::tui::*;
 
     let (w, h) = size;
-    root.try_set_size(w as isize, h as isize);
-    root.render_to_stdout();
+
+    let mut scroll = content.scrolled(state.scroll).with_scrollbar(state.theme.heading);
+    scroll.try_set_size(w, h);
+    let pos = scroll.pos();
+
+    let mut root = scroll.centered().with_background(state.theme.background);
+    root.try_set_size(w, h);
+    screen.render(&mut root);
+
+    pos
 }
 
 fn german_weekday(day: chrono::Weekday) -> &'static str {
@@ -504,7 +1087,7 @@ fn german_weekday(day: chrono::Weekday) -> &'static str {
     }
 }
 
-fn render_errors(size: (isize, isize), state: &AppState) {
+fn render_errors(screen: &mut tui::VScreen, size: (isize, isize), state: &AppState) {
     use crate::tui::*;
     let theme = &state.theme;
 
@@ -522,5 +1105,5 @@ fn render_errors(size: (isize, isize), state: &AppState) {
     let (w, h) = size;
 
     root.try_set_size(w as isize, h as isize);
-    root.render_to_stdout();
+    screen.render(&mut root);
 }
@@ -1,31 +1,112 @@
 
 
 
+use nix::libc;
 use nix::sys::signal;
 use nix::sys::termios;
 
-pub fn term_setup() -> bool {
-    println!("\x1B[?25h");
-    println!("\x1B[?25l");
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
 
-    let mut term = match termios::tcgetattr(0) {
-        Ok(o) => o,
-        Err(_) => return false,
-    };
+/// The terminal as it was before the guard, for the panic hook and signal handlers.
+static mut ORIGINAL: Option<libc::termios> = None;
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
 
-    term.local_flags &= !termios::LocalFlags::ICANON;
-    term.local_flags &= !termios::LocalFlags::ECHO;
+/// Switches to the alternate screen without echo and line buffering, and
+/// restores the terminal when dropped, on panics and on fatal signals.
+pub struct TermGuard {
+    _private: (),
+}
+
+impl TermGuard {
+    pub fn new() -> Self {
+        if let Ok(term) = termios::tcgetattr(0) {
+            unsafe {
+                ORIGINAL = Some(term.clone().into());
+            }
+
+            let mut raw = term;
+            raw.local_flags &= !termios::LocalFlags::ICANON;
+            raw.local_flags &= !termios::LocalFlags::ECHO;
+            let _ = termios::tcsetattr(0, termios::SetArg::TCSANOW, &raw);
+        }
 
-    let ret = match termios::tcsetattr(0, termios::SetArg::TCSANOW, &term) {
-        Ok(_) => true,
-        Err(_) => false,
-    };
+        write_raw(b"\x1B[?1049h\x1B[?25l");
+        ACTIVE.store(true, Ordering::SeqCst);
 
-    let _ = clear_buffer();
+        let _ = clear_buffer();
+
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+
+            std::panic::set_hook(Box::new(move |info| {
+                let was_active = restore();
+                previous(info);
+
+                // The TUI can't go on without its terminal, whichever thread panicked.
+                if was_active {
+                    std::process::exit(101);
+                }
+            }));
+        });
+
+        for sig in [signal::SIGTERM, signal::SIGHUP, signal::SIGQUIT].iter() {
+            register_for(*sig, fatal_signal);
+        }
 
-    register_for_sigint(set_sigint);
+        TermGuard { _private: () }
+    }
+}
 
-    ret
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        restore();
+        let _ = clear_buffer();
+    }
+}
+
+/// Puts the terminal back like it was before `TermGuard::new`, false if it already was.
+/// Only uses async-signal-safe calls, signal handlers need it too.
+pub fn restore() -> bool {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return false;
+    }
+
+    write_raw(b"\x1B[0m\x1B[?25h\x1B[?1049l");
+
+    unsafe {
+        if let Some(term) = *std::ptr::addr_of!(ORIGINAL) {
+            libc::tcsetattr(0, libc::TCSANOW, &term);
+        }
+    }
+
+    true
+}
+
+fn write_raw(bytes: &[u8]) {
+    unsafe {
+        libc::write(1, bytes.as_ptr() as *const libc::c_void, bytes.len());
+    }
+}
+
+extern "C" fn fatal_signal(sig: c_int) {
+    restore();
+
+    // Dies of the signal as it would have without us.
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
+
+fn register_for(sig: signal::Signal, handler: extern "C" fn(c_int)) {
+    use nix::sys::signal::*;
+
+    let action = SigAction::new(SigHandler::Handler(handler), SaFlags::empty(), SigSet::empty());
+    unsafe {
+        let _ = sigaction(sig, &action);
+    }
 }
 
 // TODO: dedup code
@@ -120,8 +201,3 @@ fn clear_buffer() -> Option<()> {
         }
     }
 }
-
-pub fn term_unsetup() {
-    println!("\x1B[?25h");
-    let _ = clear_buffer();
-}
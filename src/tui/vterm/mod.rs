@@ -27,6 +27,15 @@ pub use self::vcenter::*;
 mod vgrid;
 pub use self::vgrid::*;
 
+mod vlayout;
+pub use self::vlayout::*;
+
+mod vscroll;
+pub use self::vscroll::*;
+
+mod vscreen;
+pub use self::vscreen::*;
+
 mod vterm;
 pub use self::vterm::*;
 
@@ -67,21 +76,6 @@ impl Default for Color {
     }
 }
 
-enum StringLike {
-    Static(&'static str),
-    Dyn(String),
-}
-
-use std::fmt::{self, Display};
-impl Display for StringLike {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            StringLike::Static(s) => s.fmt(f),
-            StringLike::Dyn(s) => s.fmt(f),
-        }
-    }
-}
-
 impl Color {
     pub fn rgb(rgb: u32) -> Self {
         Color::Custom(
@@ -91,62 +85,36 @@ impl Color {
         )
     }
 
-    fn to_ansi_foreground(&self) -> impl Display {
-        let stat = match self {
-            Color::None => "\x1B[0m",
-
-            Color::Black => "\x1B[30m",
-            Color::Red => "\x1B[31m",
-            Color::Green => "\x1B[32m",
-            Color::Yellow => "\x1B[33m",
-            Color::Blue => "\x1B[34m",
-            Color::Magenta => "\x1B[35m",
-            Color::Cyan => "\x1B[36m",
-            Color::White => "\x1B[37m",
-            Color::BrightBlack => "\x1B[90m",
-            Color::BrightRed => "\x1B[91m",
-            Color::BrightGreen => "\x1B[92m",
-            Color::BrightYellow => "\x1B[93m",
-            Color::BrightBlue => "\x1B[94m",
-            Color::BrightMagenta => "\x1B[95m",
-            Color::BrightCyan => "\x1B[96m",
-            Color::BrightWhite => "\x1B[97m",
-            Color::Bits8(c) => return StringLike::Dyn(format!("\x1B[38;5;{}m", c)),
-            Color::Custom(r, g, b) => {
-                return StringLike::Dyn(format!("\x1B[38;2;{};{};{}m", r, g, b))
-            }
-        };
-
-        StringLike::Static(stat)
-    }
-
-    fn to_ansi_background(&self) -> impl Display {
-        let stat = match self {
-            Color::None => "\x1B[0m",
-
-            Color::Black => "\x1B[40m",
-            Color::Red => "\x1B[41m",
-            Color::Green => "\x1B[42m",
-            Color::Yellow => "\x1B[43m",
-            Color::Blue => "\x1B[44m",
-            Color::Magenta => "\x1B[45m",
-            Color::Cyan => "\x1B[46m",
-            Color::White => "\x1B[47m",
-            Color::BrightBlack => "\x1B[100m",
-            Color::BrightRed => "\x1B[101m",
-            Color::BrightGreen => "\x1B[102m",
-            Color::BrightYellow => "\x1B[103m",
-            Color::BrightBlue => "\x1B[104m",
-            Color::BrightMagenta => "\x1B[105m",
-            Color::BrightCyan => "\x1B[106m",
-            Color::BrightWhite => "\x1B[107m",
-            Color::Bits8(c) => return StringLike::Dyn(format!("\x1B[48;5;{}m", c)),
+    /// Parameters of a SGR escape sequence, `None` is the terminal's default.
+    fn sgr(&self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+
+        let code = match self {
+            Color::None => base + 9,
+
+            Color::Black => base,
+            Color::Red => base + 1,
+            Color::Green => base + 2,
+            Color::Yellow => base + 3,
+            Color::Blue => base + 4,
+            Color::Magenta => base + 5,
+            Color::Cyan => base + 6,
+            Color::White => base + 7,
+            Color::BrightBlack => base + 60,
+            Color::BrightRed => base + 61,
+            Color::BrightGreen => base + 62,
+            Color::BrightYellow => base + 63,
+            Color::BrightBlue => base + 64,
+            Color::BrightMagenta => base + 65,
+            Color::BrightCyan => base + 66,
+            Color::BrightWhite => base + 67,
+            Color::Bits8(c) => return format!("{};5;{}", base + 8, c),
             Color::Custom(r, g, b) => {
-                return StringLike::Dyn(format!("\x1B[48;2;{};{};{}m", r, g, b))
+                return format!("{};2;{};{};{}", base + 8, r, g, b)
             }
         };
 
-        StringLike::Static(stat)
+        code.to_string()
     }
 }
 
@@ -186,56 +154,8 @@ pub trait Widget {
     fn try_set_size(&mut self, w: isize, h: isize);
     fn get(&mut self, x: isize, y: isize) -> Option<VChar>;
 
+    /// Draws everything, `VScreen` only draws what changed since the last frame.
     fn render_to_stdout(&mut self) {
-        use std::fmt::Write;
-
-        let mut out = String::new();
-        let mut last_foreground = Color::None;
-        let mut last_background = Color::None;
-
-        write!(out, "\x1B[0;0H").unwrap(); // Goto Home
-        write!(out, "{}", last_foreground.to_ansi_foreground()).unwrap();
-        write!(out, "{}", last_background.to_ansi_foreground()).unwrap();
-
-        let (w, h) = self.size();
-
-        if w <= 0 || h <= 0 {
-            return;
-        }
-
-        for y in 0..h {
-            for x in 0..w {
-                let vch = self.get(x, y).unwrap_or(VChar::SPACE);
-
-                if last_foreground != vch.foreground {
-                    last_foreground = vch.foreground;
-
-                    if last_foreground == Color::None {
-                        last_background = Color::None;
-                    }
-
-                    write!(out, "{}", last_foreground.to_ansi_foreground()).unwrap();
-                }
-
-                if last_background != vch.background {
-                    last_background = vch.background;
-
-                    write!(out, "{}", last_background.to_ansi_background()).unwrap();
-
-                    if last_background == Color::None {
-                        write!(out, "{}", last_foreground.to_ansi_foreground()).unwrap();
-                    }
-                }
-                write!(out, "{}", vch.char).unwrap();
-            }
-
-            if y != h - 1 {
-                write!(out, "\n").unwrap();
-            }
-        }
-
-        let mut stdout = io::stdout();
-        write!(stdout, "{}", out).unwrap();
-        stdout.flush().unwrap();
+        VScreen::new().render(self);
     }
 }
@@ -48,8 +48,9 @@ impl<W: Widget> Widget for Center<W> {
     }
 
     fn get(&mut self, x: isize, y: isize) -> Option<VChar> {
-        let offsetx = self.w - self.cw;
-        let offsety = self.h - self.ch;
+        // Content larger than the space starts at the top left, a `Scroll` shows the rest.
+        let offsetx = (self.w - self.cw).max(0);
+        let offsety = (self.h - self.ch).max(0);
 
         self.inner
             .get(x - offsetx / 2, y - offsety / 2)
@@ -4,6 +4,8 @@ use super::*;
 #[derive(Default)]
 pub struct GridH {
     pub content: Vec<Box<dyn Widget>>,
+    /// One per child, missing ones are the default even share.
+    pub constraints: Vec<Constraint>,
 }
 
 impl GridH {
@@ -11,13 +13,31 @@ impl GridH {
         Default::default()
     }
 
-    pub fn add<W: 'static + Widget>(mut self, c: W) -> Self {
-        self.content.push(Box::new(c));
+    pub fn add<W: 'static + Widget>(self, c: W) -> Self {
+        self.add_with(c, Constraint::default())
+    }
+
+    pub fn add_with<W: 'static + Widget>(mut self, c: W, constraint: Constraint) -> Self {
+        self.push_with(c, constraint);
         self
     }
 
     pub fn push<W: 'static + Widget>(&mut self, c: W) {
+        self.push_with(c, Constraint::default());
+    }
+
+    pub fn push_with<W: 'static + Widget>(&mut self, c: W, constraint: Constraint) {
+        self.constraints.resize(self.content.len(), Constraint::default());
         self.content.push(Box::new(c));
+        self.constraints.push(constraint);
+    }
+
+    fn sizes(&self, total: isize) -> Vec<isize> {
+        let constraints = (0..self.content.len())
+            .map(|i| self.constraints.get(i).cloned().unwrap_or_default())
+            .collect::<Vec<Constraint>>();
+
+        layout(total, &constraints)
     }
 }
 
@@ -37,15 +57,10 @@ impl Widget for GridH {
     }
 
     fn try_set_size(&mut self, w: isize, h: isize) {
-        let len = self.content.len() as isize;
-        if len == 0 {
-            return;
-        }
+        let sizes = self.sizes(w);
 
-        let avg_w = w / len;
-
-        for c in self.content.iter_mut() {
-            c.try_set_size(avg_w, h)
+        for (c, cw) in self.content.iter_mut().zip(sizes) {
+            c.try_set_size(cw, h)
         }
     }
 
@@ -66,6 +81,8 @@ impl Widget for GridH {
 #[derive(Default)]
 pub struct GridV {
     pub content: Vec<Box<dyn Widget>>,
+    /// One per child, missing ones are the default even share.
+    pub constraints: Vec<Constraint>,
 }
 
 impl GridV {
@@ -73,13 +90,31 @@ impl GridV {
         Default::default()
     }
 
-    pub fn add<W: 'static + Widget>(mut self, c: W) -> Self {
-        self.content.push(Box::new(c));
+    pub fn add<W: 'static + Widget>(self, c: W) -> Self {
+        self.add_with(c, Constraint::default())
+    }
+
+    pub fn add_with<W: 'static + Widget>(mut self, c: W, constraint: Constraint) -> Self {
+        self.push_with(c, constraint);
         self
     }
 
     pub fn push<W: 'static + Widget>(&mut self, c: W) {
+        self.push_with(c, Constraint::default());
+    }
+
+    pub fn push_with<W: 'static + Widget>(&mut self, c: W, constraint: Constraint) {
+        self.constraints.resize(self.content.len(), Constraint::default());
         self.content.push(Box::new(c));
+        self.constraints.push(constraint);
+    }
+
+    fn sizes(&self, total: isize) -> Vec<isize> {
+        let constraints = (0..self.content.len())
+            .map(|i| self.constraints.get(i).cloned().unwrap_or_default())
+            .collect::<Vec<Constraint>>();
+
+        layout(total, &constraints)
     }
 }
 
@@ -99,15 +134,10 @@ impl Widget for GridV {
     }
 
     fn try_set_size(&mut self, w: isize, h: isize) {
-        let len = self.content.len() as isize;
-        if len == 0 {
-            return;
-        }
+        let sizes = self.sizes(h);
 
-        let avg_h = h / len;
-
-        for c in self.content.iter_mut() {
-            c.try_set_size(w, avg_h)
+        for (c, ch) in self.content.iter_mut().zip(sizes) {
+            c.try_set_size(w, ch)
         }
     }
 
@@ -0,0 +1,163 @@
+/// How much of a grid a child gets, its width in `GridH` and its height in `GridV`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Size {
+    Fixed(isize),
+    /// Of the whole grid.
+    Percent(isize),
+    /// A share of what the fixed and percentage children leave over.
+    Flex(isize),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Constraint {
+    pub size: Size,
+    pub min: isize,
+    pub max: Option<isize>,
+}
+
+impl Default for Constraint {
+    /// Every child the same, like a grid without constraints.
+    fn default() -> Self {
+        Constraint::flex(1)
+    }
+}
+
+impl Constraint {
+    pub fn fixed(n: isize) -> Self {
+        Constraint { size: Size::Fixed(n), min: 0, max: None }
+    }
+
+    pub fn percent(p: isize) -> Self {
+        Constraint { size: Size::Percent(p), min: 0, max: None }
+    }
+
+    pub fn flex(weight: isize) -> Self {
+        Constraint { size: Size::Flex(weight.max(0)), min: 0, max: None }
+    }
+
+    pub fn min(mut self, min: isize) -> Self {
+        self.min = min;
+        self
+    }
+
+    pub fn max(mut self, max: isize) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    fn clamp(&self, n: isize) -> isize {
+        let n = match self.max {
+            Some(max) => n.min(max),
+            None => n,
+        };
+
+        n.max(self.min).max(0)
+    }
+}
+
+/// Splits `total` among children with `constraints`. Fixed and percentage
+/// children come first, flex children share the rest by weight, rounded down
+/// like the even split grids always had. If the minimums don't fit, the grid
+/// ends up larger than `total`.
+pub fn layout(total: isize, constraints: &[Constraint]) -> Vec<isize> {
+    let mut sizes = vec![0; constraints.len()];
+    // Flex children whose size is settled, because they hit min or max.
+    let mut done = vec![false; constraints.len()];
+
+    let mut left = total;
+    for (i, c) in constraints.iter().enumerate() {
+        let size = match c.size {
+            Size::Fixed(n) => n,
+            Size::Percent(p) => total * p / 100,
+            Size::Flex(_) => continue,
+        };
+
+        sizes[i] = c.clamp(size);
+        done[i] = true;
+        left -= sizes[i];
+    }
+
+    // Freezing one child at a bound changes the share of the others, so repeat until none moves.
+    loop {
+        let weights: isize = constraints
+            .iter()
+            .zip(done.iter())
+            .filter(|(_, done)| !**done)
+            .map(|(c, _)| weight(c))
+            .sum();
+
+        let mut frozen = vec![];
+
+        for (i, c) in constraints.iter().enumerate() {
+            if done[i] {
+                continue;
+            }
+
+            let share = if weights > 0 { left.max(0) * weight(c) / weights } else { 0 };
+            sizes[i] = c.clamp(share);
+
+            if sizes[i] != share {
+                frozen.push(i);
+            }
+        }
+
+        if frozen.is_empty() {
+            break;
+        }
+
+        for i in frozen {
+            done[i] = true;
+            left -= sizes[i];
+        }
+    }
+
+    sizes
+}
+
+fn weight(c: &Constraint) -> isize {
+    match c.size {
+        Size::Flex(w) => w,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_the_even_split() {
+        assert_eq!(layout(80, &[Constraint::default(); 3]), vec![26, 26, 26]);
+        assert_eq!(layout(80, &[Constraint::default(); 4]), vec![20, 20, 20, 20]);
+    }
+
+    #[test]
+    fn flex_shares_what_fixed_leaves() {
+        let constraints = [Constraint::fixed(20), Constraint::flex(1), Constraint::flex(2)];
+        assert_eq!(layout(80, &constraints), vec![20, 20, 40]);
+
+        let constraints = [Constraint::percent(25), Constraint::flex(1)];
+        assert_eq!(layout(80, &constraints), vec![20, 60]);
+    }
+
+    #[test]
+    fn bounds_clamp_and_pass_the_rest_on() {
+        let constraints = [Constraint::flex(1).max(10), Constraint::flex(1)];
+        assert_eq!(layout(80, &constraints), vec![10, 70]);
+
+        let constraints = [Constraint::flex(1).min(60), Constraint::flex(1)];
+        assert_eq!(layout(80, &constraints), vec![60, 20]);
+
+        assert_eq!(layout(80, &[Constraint::fixed(30).max(20)]), vec![20]);
+        assert_eq!(layout(80, &[Constraint::fixed(5).min(10)]), vec![10]);
+    }
+
+    #[test]
+    fn minimums_overflow_the_total() {
+        let constraints = [Constraint::fixed(50), Constraint::flex(1).min(40)];
+        assert_eq!(layout(80, &constraints), vec![50, 40]);
+
+        let constraints = [Constraint::fixed(100), Constraint::flex(1)];
+        assert_eq!(layout(80, &constraints), vec![100, 0]);
+    }
+}
@@ -0,0 +1,174 @@
+use super::*;
+
+/// Keeps the last frame on the terminal, so a render only writes the cells
+/// that changed since.
+pub struct VScreen {
+    width: isize,
+    height: isize,
+    cells: Vec<VChar>,
+}
+
+impl VScreen {
+    pub fn new() -> Self {
+        VScreen {
+            width: 0,
+            height: 0,
+            cells: vec![],
+        }
+    }
+
+    /// Forgets the last frame, the next render redraws everything.
+    pub fn invalidate(&mut self) {
+        self.width = 0;
+        self.height = 0;
+        self.cells.clear();
+    }
+
+    pub fn render<W: Widget + ?Sized>(&mut self, widget: &mut W) {
+        let out = self.diff(widget);
+
+        if out.is_empty() {
+            return;
+        }
+
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// The escape sequences that turn the last frame into `widget`.
+    fn diff<W: Widget + ?Sized>(&mut self, widget: &mut W) -> String {
+        let (w, h) = widget.size();
+
+        if w <= 0 || h <= 0 {
+            return String::new();
+        }
+
+        let mut frame = Vec::with_capacity((w * h) as usize);
+        for y in 0..h {
+            for x in 0..w {
+                frame.push(widget.get(x, y).unwrap_or(VChar::SPACE));
+            }
+        }
+
+        let mut out = String::new();
+
+        // Whatever is on the terminal doesn't match the old frame anymore.
+        let full = (w, h) != (self.width, self.height);
+        if full {
+            out.push_str("\x1B[0m\x1B[2J");
+        }
+
+        // Unknown until the first escape sequence.
+        let mut colors = None;
+        let mut cursor = None;
+
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) as usize;
+                let vch = frame[i];
+
+                if !full && self.cells[i] == vch {
+                    continue;
+                }
+
+                if cursor != Some((x, y)) {
+                    out.push_str(&format!("\x1B[{};{}H", y + 1, x + 1));
+                }
+
+                if colors != Some((vch.foreground, vch.background)) {
+                    out.push_str(&sgr(vch.foreground, vch.background));
+                    colors = Some((vch.foreground, vch.background));
+                }
+
+                out.push(vch.char);
+
+                // In the last column the terminal waits to wrap, better move explicitly.
+                cursor = if x + 1 < w { Some((x + 1, y)) } else { None };
+            }
+        }
+
+        self.width = w;
+        self.height = h;
+        self.cells = frame;
+
+        if !out.is_empty() {
+            out.push_str("\x1B[0m");
+        }
+
+        out
+    }
+}
+
+impl Default for VScreen {
+    fn default() -> Self {
+        VScreen::new()
+    }
+}
+
+/// Both colors in one escape sequence.
+fn sgr(foreground: Color, background: Color) -> String {
+    format!("\x1B[{};{}m", foreground.sgr(false), background.sgr(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Cells(isize, isize, Vec<VChar>);
+
+    impl Widget for Cells {
+        fn size(&mut self) -> (isize, isize) {
+            (self.0, self.1)
+        }
+
+        fn try_set_size(&mut self, _w: isize, _h: isize) {}
+
+        fn get(&mut self, x: isize, y: isize) -> Option<VChar> {
+            self.2.get((y * self.0 + x) as usize).copied()
+        }
+    }
+
+    fn cells(w: isize, h: isize) -> Cells {
+        Cells(w, h, vec![VChar::new('a', Color::None); (w * h) as usize])
+    }
+
+    #[test]
+    fn first_frame_clears_and_draws_everything() {
+        let mut screen = VScreen::new();
+        let out = screen.diff(&mut cells(3, 2));
+
+        assert!(out.starts_with("\x1B[0m\x1B[2J"));
+        assert_eq!(out.matches('a').count(), 6);
+    }
+
+    #[test]
+    fn same_frame_writes_nothing() {
+        let mut screen = VScreen::new();
+        let mut widget = cells(3, 2);
+        screen.diff(&mut widget);
+
+        assert_eq!(screen.diff(&mut widget), "");
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let mut screen = VScreen::new();
+        let mut widget = cells(3, 2);
+        screen.diff(&mut widget);
+
+        widget.2[4] = VChar::new('b', Color::Red);
+        let expected = format!("\x1B[2;2H{}b\x1B[0m", sgr(Color::Red, Color::None));
+        assert_eq!(screen.diff(&mut widget), expected);
+    }
+
+    #[test]
+    fn resize_redraws_everything() {
+        let mut screen = VScreen::new();
+        screen.diff(&mut cells(3, 2));
+
+        let out = screen.diff(&mut cells(2, 2));
+        assert!(out.starts_with("\x1B[0m\x1B[2J"));
+        assert_eq!(out.matches('a').count(), 4);
+    }
+}
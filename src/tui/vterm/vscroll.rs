@@ -0,0 +1,137 @@
+use super::*;
+
+/// Where a `Scroll` is, kept by the app between frames.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct ScrollPos {
+    pub x: isize,
+    pub y: isize,
+    /// Lines visible at once, known after the last layout.
+    pub page: isize,
+}
+
+impl ScrollPos {
+    pub fn lines(&mut self, n: isize) {
+        self.y += n;
+    }
+
+    /// Keeps one line of the last page in view.
+    pub fn pages(&mut self, n: isize) {
+        self.y += n * (self.page - 1).max(1);
+    }
+
+    pub fn columns(&mut self, n: isize) {
+        self.x += n;
+    }
+}
+
+/// Shows the part of `inner` at `pos` that fits, with a scrollbar if wanted.
+pub struct Scroll<W: Widget> {
+    inner: W,
+    pos: ScrollPos,
+    /// The viewport, `None` until `try_set_size`.
+    view: Option<(isize, isize)>,
+    scrollbar: Option<Color>,
+    /// Whether the scrollbar takes the last column right now.
+    bar: bool,
+}
+
+impl<W: Widget> Scroll<W> {
+    pub fn new(inner: W, pos: ScrollPos) -> Self {
+        Scroll {
+            inner,
+            pos,
+            view: None,
+            scrollbar: None,
+            bar: false,
+        }
+    }
+
+    /// Draws a scrollbar in `color` when `inner` is taller than the viewport.
+    pub fn with_scrollbar(mut self, color: Color) -> Self {
+        self.scrollbar = Some(color);
+        self
+    }
+
+    /// The position clamped to the content, with the page size.
+    pub fn pos(&self) -> ScrollPos {
+        self.pos
+    }
+
+    pub fn scroll_lines(&mut self, n: isize) {
+        self.pos.lines(n);
+        self.clamp();
+    }
+
+    pub fn scroll_pages(&mut self, n: isize) {
+        self.pos.pages(n);
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        let (w, h) = match self.view {
+            Some(view) => view,
+            None => return,
+        };
+
+        let (cw, ch) = self.inner.size();
+        let w = if self.bar { w - 1 } else { w };
+
+        self.pos.x = self.pos.x.min(cw - w).max(0);
+        self.pos.y = self.pos.y.min(ch - h).max(0);
+        self.pos.page = h;
+    }
+}
+
+impl<W: Widget> Widget for Scroll<W> {
+    fn size(&mut self) -> (isize, isize) {
+        let (cw, ch) = self.inner.size();
+
+        match self.view {
+            Some((w, h)) if self.bar => (cw.min(w - 1) + 1, ch.min(h)),
+            Some((w, h)) => (cw.min(w), ch.min(h)),
+            None => (cw, ch),
+        }
+    }
+
+    fn try_set_size(&mut self, w: isize, h: isize) {
+        self.view = Some((w, h));
+
+        self.inner.try_set_size(w, h);
+        self.bar = self.scrollbar.is_some() && self.inner.size().1 > h && w > 1;
+
+        if self.bar {
+            self.inner.try_set_size(w - 1, h);
+        }
+
+        self.clamp();
+    }
+
+    fn get(&mut self, x: isize, y: isize) -> Option<VChar> {
+        let (w, h) = self.size();
+
+        if x < 0 || y < 0 || x >= w || y >= h {
+            return None;
+        }
+
+        if self.bar && x == w - 1 {
+            let ch = self.inner.size().1;
+            let thumb = (h * h / ch).max(1);
+            let start = self.pos.y * (h - thumb) / (ch - h).max(1);
+
+            let c = if y >= start && y < start + thumb { '█' } else { '│' };
+            return Some(VChar::new(c, self.scrollbar.unwrap_or(Color::None)));
+        }
+
+        self.inner.get(x + self.pos.x, y + self.pos.y)
+    }
+}
+
+pub trait WithScroll<W: Widget> {
+    fn scrolled(self, pos: ScrollPos) -> Scroll<W>;
+}
+
+impl<W: Widget + Sized> WithScroll<W> for W {
+    fn scrolled(self, pos: ScrollPos) -> Scroll<W> {
+        Scroll::new(self, pos)
+    }
+}
@@ -0,0 +1,283 @@
+use hs_crawler::canteen_plan;
+use hs_crawler::timetable;
+
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::fetch::{self, Fetched, Selection};
+use crate::ui::http::{self, Request, Response};
+use crate::ui::json::{self, iso_date, JsonError, JsonLecture, JsonMeal};
+use crate::ui::plain::parse_date;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use serde::Serialize;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+
+/// The course index hardly ever changes.
+const COURSES_MAX_AGE_SEC: u64 = 6 * 60 * 60;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("api")
+        .about("Serves timetable and canteen plan as JSON on localhost.")
+        .arg(Arg::with_name("bind")
+            .short("b")
+            .long("bind")
+            .takes_value(true)
+            .default_value("127.0.0.1:8643")
+            .help("Address to listen on.")
+        ).arg(Arg::with_name("refresh")
+            .long("refresh")
+            .takes_value(true)
+            .help("Minutes a cache is used before the crawler runs again, refresh_minutes of the config by default.")
+        )
+}
+
+struct Api {
+    default_course: String,
+    max_age: Duration,
+    /// One crawl per course at a time, concurrent requests for it wait for its cache.
+    crawling: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    courses: Mutex<Option<(Instant, Vec<String>)>>,
+}
+
+#[derive(Serialize)]
+struct TimetableResponse {
+    schema_version: u32,
+    course: String,
+    from: String,
+    to: String,
+    timetable: BTreeMap<String, Vec<JsonLecture>>,
+    errors: Vec<JsonError>,
+}
+
+#[derive(Serialize)]
+struct CanteenResponse {
+    schema_version: u32,
+    from: String,
+    to: String,
+    canteen: BTreeMap<String, Vec<JsonMeal>>,
+    errors: Vec<JsonError>,
+}
+
+#[derive(Serialize)]
+struct CoursesResponse {
+    courses: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    version: &'static str,
+    course: String,
+    cache_age_sec: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    let course = config.course();
+    let bind = matches.value_of("bind").unwrap_or("127.0.0.1:8643").to_string();
+    let refresh: u64 = match matches.value_of("refresh") {
+        Some(minutes) => minutes.parse().map_err(|_| "--refresh expects minutes.")?,
+        None => config.refresh_minutes,
+    };
+
+    let api = Arc::new(Api {
+        default_course: course.to_string(),
+        max_age: Duration::from_secs(refresh * 60),
+        crawling: Mutex::new(HashMap::new()),
+        courses: Mutex::new(None),
+    });
+
+    eprintln!("Serving JSON on http://{}/timetable, /canteen, /courses and /health", bind);
+
+    http::serve(&bind, move |req| handle(&api, req))
+}
+
+fn handle(api: &Api, req: &Request) -> Response {
+    let res = match req.path.as_str() {
+        "/health" => health(api),
+        "/courses" => courses(api),
+        "/timetable" => timetable(api, &req.query),
+        "/canteen" => canteen(api, &req.query),
+        _ => Err((404, "Try /timetable, /canteen, /courses or /health.".to_string())),
+    };
+
+    match res {
+        Ok(res) => res,
+        Err((status, error)) => to_json(status, &ErrorResponse { error }),
+    }
+}
+
+type ApiResult = Result<Response, (u16, String)>;
+
+fn health(api: &Api) -> ApiResult {
+    Ok(to_json(200, &HealthResponse {
+        status: "ok",
+        version: crate::VERSION,
+        course: api.default_course.clone(),
+        cache_age_sec: cache::cache_age(&api.default_course).map(|a| a.as_secs()),
+    }))
+}
+
+fn courses(api: &Api) -> ApiResult {
+    let courses = known_courses(api).map_err(|e| (502, e))?;
+
+    Ok(to_json(200, &CoursesResponse { courses }))
+}
+
+/// Every course of the index, which is crawled again after `COURSES_MAX_AGE_SEC`.
+fn known_courses(api: &Api) -> Result<Vec<String>, String> {
+    let mut cached = api.courses.lock().unwrap();
+
+    let stale = match *cached {
+        Some((at, _)) => at.elapsed() > Duration::from_secs(COURSES_MAX_AGE_SEC),
+        None => true,
+    };
+
+    if stale {
+        match timetable::courses() {
+            Ok(courses) => *cached = Some((Instant::now(), courses)),
+            // Better old than nothing.
+            Err(_) if cached.is_some() => (),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    Ok(cached.as_ref().map(|(_, c)| c.clone()).unwrap_or_default())
+}
+
+fn timetable(api: &Api, query: &HashMap<String, String>) -> ApiResult {
+    let course = match query.get("course") {
+        Some(course) => course.to_uppercase(),
+        None => api.default_course.clone(),
+    };
+
+    if !fetch::is_valid_course(&course) {
+        return Err((400, "Invalid course name.".to_string()));
+    }
+
+    let (from, to) = range(query)?;
+    let mut fetched = load(api, &course)?;
+    let week = fetched.timetable.remove(&course).unwrap_or_default();
+
+    if week.is_empty() {
+        // Only the index tells whether the crawl failed or the course doesn't exist.
+        return match (fetched.errors.iter().find(|e| e.source == "timetable"), known_courses(api)) {
+            (Some(e), Ok(known)) if known.contains(&course) => Err((502, e.message.clone())),
+            (Some(e), Err(_)) => Err((502, e.message.clone())),
+            _ => Err((404, format!("No timetable for {}.", course))),
+        };
+    }
+
+    let timetable = week
+        .iter()
+        .filter(|(d, _)| **d >= from && **d <= to)
+        .map(|(d, day)| (
+            iso_date(d),
+            timetable::lectures(&course, day).into_iter().map(JsonLecture::from).collect()))
+        .collect();
+
+    Ok(to_json(200, &TimetableResponse {
+        schema_version: json::SCHEMA_VERSION,
+        course,
+        from: iso_date(&from),
+        to: iso_date(&to),
+        timetable,
+        errors: fetched.errors.into_iter().map(JsonError::from).collect(),
+    }))
+}
+
+fn canteen(api: &Api, query: &HashMap<String, String>) -> ApiResult {
+    let (from, to) = range(query)?;
+    let fetched = load(api, &api.default_course)?;
+
+    if fetched.canteen.is_empty() {
+        return Err((503, "No canteen plan yet.".to_string()));
+    }
+
+    let canteen = fetched
+        .canteen
+        .iter()
+        .filter(|(d, _)| **d >= from && **d <= to)
+        .map(|(d, day)| (
+            iso_date(d),
+            canteen_plan::meals(day).into_iter().map(JsonMeal::from).collect()))
+        .collect();
+
+    Ok(to_json(200, &CanteenResponse {
+        schema_version: json::SCHEMA_VERSION,
+        from: iso_date(&from),
+        to: iso_date(&to),
+        canteen,
+        errors: fetched.errors.into_iter().map(JsonError::from).collect(),
+    }))
+}
+
+/// `from` and `to` of the query, both inclusive. Defaults to this and next week.
+fn range(query: &HashMap<String, String>) -> Result<(Date<Local>, Date<Local>), (u16, String)> {
+    let monday = fetch::WeekQuery::This.monday();
+
+    let from = match query.get("from") {
+        Some(from) => parse_date(from).map_err(|e| (400, e))?,
+        None => monday,
+    };
+    let to = match query.get("to") {
+        Some(to) => parse_date(to).map_err(|e| (400, e))?,
+        None => monday + chrono::Duration::days(13),
+    };
+
+    if from > to {
+        return Err((400, "from is after to.".to_string()));
+    }
+
+    Ok((from, to))
+}
+
+/// The cache of `course` while it is fresh, otherwise crawled again.
+fn load(api: &Api, course: &str) -> Result<Fetched, (u16, String)> {
+    let crawling = {
+        let mut locks = api.crawling.lock().unwrap();
+        // Locks nobody waits for anymore, so unknown courses don't pile up.
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(course.to_string()).or_default().clone()
+    };
+    let _crawling = crawling.lock().unwrap();
+
+    let fresh = cache::cache_age(course)
+        .map(|age| age < api.max_age)
+        .unwrap_or(false);
+
+    if fresh {
+        if let Ok(Some(data)) = cache::read_cache(course) {
+            return Ok(Fetched::from_cache(course, data));
+        }
+    }
+
+    let fetched = fetch::fetch_cached(&Selection::default(), &[course.to_string()]);
+
+    if fetched.timetable.is_empty() && fetched.canteen.is_empty() {
+        if let Some(e) = fetched.errors.first() {
+            return Err((502, e.message.clone()));
+        }
+    }
+
+    Ok(fetched)
+}
+
+fn to_json<T: Serialize>(status: u16, body: &T) -> Response {
+    match serde_json::to_string(body) {
+        Ok(body) => Response::new(status, JSON_CONTENT_TYPE, body),
+        Err(e) => Response::text(500, &e.to_string()),
+    }
+}
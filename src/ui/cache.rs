@@ -1,16 +1,40 @@
-
 use chrono::prelude::*;
 
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::fcntl::{flock, FlockArg};
 
 use dirs;
 use serde_json;
 
+use crate::ui::config;
+use crate::ui::fetch::{monday_of, FetchTimes};
 use crate::AppData;
 
 use std::num::Wrapping;
 
+/// Version of the files below `cache_dir`. The flat `hs_app.{hash}.json`
+/// files before were version 1.
+const CACHE_VERSION: u32 = 2;
+
+/// Days older than this are dropped on write.
+const MAX_AGE_DAYS: i64 = 30;
+
+/// One source of one course (or canteen) on disk.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CacheFile {
+    version: u32,
+    /// When each week was fetched, by its monday.
+    fetched: BTreeMap<NaiveDate, DateTime<Local>>,
+    days: BTreeMap<NaiveDate, Vec<String>>,
+}
+
+/// Layout of version 1, only read to migrate.
 #[derive(Serialize, Deserialize, Debug)]
 struct AppDataStorage {
     canteen: HashMap<DateTime<Local>, Vec<String>>,
@@ -37,54 +61,465 @@ fn shitty_hash(input : &str) -> u64 {
     return initial.0;
 }
 
-pub fn read_cache(course : &str) -> Result<Option<AppData>, String> {
+/// `{cache dir}/hs_app`, with one directory per source.
+pub fn cache_dir() -> Result<PathBuf, String> {
+    let mut path = dirs::cache_dir().ok_or("Unable to find cache dir.")?;
+    path.push("hs_app");
+
+    Ok(path)
+}
+
+/// Course and canteen names end up in file names.
+fn file_name(name : &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect::<String>();
+
+    format!("{}.json", name)
+}
+
+/// The timetable of `course`.
+pub fn cache_path(course : &str) -> Result<PathBuf, String> {
+    Ok(cache_dir()?.join("timetable").join(file_name(course)))
+}
+
+/// The plan of the canteen in use, shared by all courses.
+pub fn canteen_path() -> Result<PathBuf, String> {
+    Ok(cache_dir()?.join("canteen").join(file_name(&config::canteen())))
+}
+
+fn legacy_path(course : &str) -> Result<PathBuf, String> {
     let mut path = dirs::cache_dir().ok_or("Unable to find cache dir.")?;
     path.push(format!("hs_app.{:X}.json", shitty_hash(course)));
 
-    if ! path.exists() {
-        return Ok(None);
-    }
+    Ok(path)
+}
 
-    let file = File::open(path)
-        .map_err(|e| e.to_string())?;
+fn quarantine_dir() -> Result<PathBuf, String> {
+    Ok(cache_dir()?.join("quarantine"))
+}
+
+/// Cache files that couldn't be read and were moved aside.
+pub fn quarantined() -> Vec<PathBuf> {
+    quarantine_dir()
+        .ok()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .map(|entries| entries.flat_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default()
+}
+
+/// Time since the cache was last written, `None` if there is none.
+pub fn cache_age(course : &str) -> Option<Duration> {
+    let modified = cache_path(course).ok()?
+        .metadata().ok()?
+        .modified().ok()?;
+
+    modified.elapsed().ok()
+}
+
+/// An advisory lock on the whole cache, released when dropped.
+pub struct Lock(File);
+
+pub fn lock(exclusive : bool) -> Result<Lock, String> {
+    let dir = cache_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
 
-    let data : AppDataStorage = serde_json::from_reader(&file)
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(dir.join("lock"))
         .map_err(|e| e.to_string())?;
 
+    let arg = if exclusive { FlockArg::LockExclusive } else { FlockArg::LockShared };
+    flock(file.as_raw_fd(), arg).map_err(|e| format!("Unable to lock cache: {}", e))?;
+
+    Ok(Lock(file))
+}
+
+/// A file below `cache_dir` as read.
+enum Read {
+    Missing,
+    Corrupt,
+    /// Written by a newer hs_app with this version, neither read nor replaced.
+    Newer(u64),
+    File(CacheFile),
+}
+
+impl Read {
+    fn ok(self) -> Option<CacheFile> {
+        match self {
+            Read::File(file) => Some(file),
+            _ => None,
+        }
+    }
+}
+
+/// The timetable of `course` with the canteen plan, `None` if the timetable isn't cached.
+pub fn read_cache(course : &str) -> Result<Option<AppData>, String> {
+    migrate_legacy(course)?;
+
+    let timetable_path = cache_path(course)?;
+    let canteen_path = canteen_path()?;
+
+    let (timetable, canteen) = read_files(&[&timetable_path, &canteen_path])?;
+
+    let timetable = match timetable {
+        Some(timetable) => timetable,
+        None => return Ok(None),
+    };
+
+    let (timetable_fetched, timetable) = split(timetable);
+    let (canteen_fetched, canteen) = split(canteen.unwrap_or_default());
+
     Ok( Some(
         AppData {
-            canteen : data.canteen.into_iter().map(|(k,v)| {
-                (k.date(), v)
-            }).collect(),
-            timetable : data.timetable.into_iter().map(|(k,v)| {
-                (k.date(), v)
-            }).collect()
+            canteen,
+            timetable,
+            canteen_fetched,
+            timetable_fetched,
         }
     ))
 }
 
-pub fn write_cache(data : &AppData, course : &str) ->  Result<(), String> {
-    let now = Local::now();
+/// Just the canteen plan, for courses without a cached timetable.
+pub fn read_canteen_cache() -> Result<Option<AppData>, String> {
+    let path = canteen_path()?;
 
-    let storage = AppDataStorage {
-        canteen: data.canteen.iter().map(|(k,v)|{
-            (k.and_hms(12,0,0),v.clone())
+    let canteen = match read_files(&[&path])?.0 {
+        Some(canteen) => canteen,
+        None => return Ok(None),
+    };
 
-            // collect old stuff v
-        }).filter(|(k,_)| now.signed_duration_since(k.clone()).num_days() < 30)
-            .collect(),
-        timetable: data.timetable.iter().map(|(k,v)|{
-            (k.and_hms(12,0,0),v.clone())
-        }).filter(|(k,_)| now.signed_duration_since(k.clone()).num_days() < 30)
-            .collect(),
+    let (canteen_fetched, canteen) = split(canteen);
+
+    Ok(Some(AppData {
+        canteen,
+        canteen_fetched,
+        ..AppData::default()
+    }))
+}
+
+/// Reads up to two files under the shared lock. Corrupt ones are quarantined
+/// afterwards under the exclusive lock, and count as missing.
+fn read_files(paths : &[&Path]) -> Result<(Option<CacheFile>, Option<CacheFile>), String> {
+    let mut read = {
+        let _lock = lock(false)?;
+        paths.iter().map(|p| read_file(p)).collect::<Vec<Read>>()
     };
 
-    let mut path = dirs::cache_dir().ok_or("Unable to find cache dir.")?;
-    path.push(format!("hs_app.{:X}.json", shitty_hash(course)));
+    if read.iter().any(|r| matches!(r, Read::Corrupt)) {
+        let _lock = lock(true)?;
 
-    let file = File::create(path)
-        .map_err(|e| e.to_string())?;
+        for (path, r) in paths.iter().zip(read.iter_mut()) {
+            if let Read::Corrupt = r {
+                // Another instance might have replaced it meanwhile.
+                *r = read_file(path);
+                if let Read::Corrupt = r {
+                    quarantine(path);
+                }
+            }
+        }
+    }
+
+    let mut read = read.into_iter().map(Read::ok);
+
+    Ok((read.next().flatten(), read.next().flatten()))
+}
 
-    serde_json::to_writer(file, &storage)
-        .map_err(|e| e.to_string())
+/// Merges `data` into the cache. Days in `data` replace cached ones, others are kept.
+pub fn write_cache(data : &AppData, course : &str) -> Result<(), String> {
+    migrate_legacy(course)?;
+
+    let _lock = lock(true)?;
+
+    // Nothing fetched and nothing cached, e.g. for a course that doesn't
+    // exist. That shouldn't leave an empty file behind.
+    if !data.timetable.is_empty() || !data.timetable_fetched.is_empty() {
+        write_merged(&cache_path(course)?, &data.timetable, &data.timetable_fetched)?;
+    }
+
+    if !data.canteen.is_empty() || !data.canteen_fetched.is_empty() {
+        write_merged(&canteen_path()?, &data.canteen, &data.canteen_fetched)?;
+    }
+
+    Ok(())
+}
+
+fn write_merged(path : &Path, days : &HashMap<Date<Local>, Vec<String>>, fetched : &FetchTimes) -> Result<(), String> {
+    let today = Local::today().naive_local();
+
+    // Called under the exclusive lock, so broken files can go right away.
+    let mut file = match read_file(path) {
+        Read::File(file) => file,
+        Read::Corrupt => {
+            quarantine(path);
+            CacheFile::default()
+        }
+        Read::Missing => CacheFile::default(),
+        Read::Newer(version) => {
+            return Err(format!(
+                "{} is from a newer hs_app (cache version {}), not overwriting it.",
+                path.display(),
+                version
+            ))
+        }
+    };
+    file.version = CACHE_VERSION;
+    file.days.extend(days.iter().map(|(k,v)| (k.naive_local(), v.clone())));
+    file.days.retain(|k,_| today.signed_duration_since(*k).num_days() < MAX_AGE_DAYS);
+
+    // Only newer times, an instance with old data shouldn't make it look fresh.
+    for (k,at) in fetched.iter() {
+        let old = file.fetched.entry(k.naive_local()).or_insert(*at);
+        if at > old {
+            *old = *at;
+        }
+    }
+    file.fetched.retain(|k,_| today.signed_duration_since(*k).num_days() < MAX_AGE_DAYS);
+
+    let out = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+    write_atomic(path, &out)
+}
+
+/// Writes to a temporary file first, so a crash never leaves half a cache.
+pub fn write_atomic(path : &Path, contents : &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+
+    let res = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    res.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Doesn't quarantine anything, that needs the exclusive lock.
+fn read_file(path : &Path) -> Read {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return Read::Missing,
+    };
+
+    let value : serde_json::Value = match serde_json::from_str(&contents) {
+        Ok(value) => value,
+        Err(_) => return Read::Corrupt,
+    };
+
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(v) if v == CACHE_VERSION as u64 => (),
+        // Written by a newer hs_app, nothing to read for us.
+        Some(v) if v > CACHE_VERSION as u64 => return Read::Newer(v),
+        _ => return Read::Corrupt,
+    }
+
+    match serde_json::from_value(value) {
+        Ok(file) => Read::File(file),
+        Err(_) => Read::Corrupt,
+    }
+}
+
+/// Moves a broken file out of the way, so it is fetched again.
+fn quarantine(path : &Path) {
+    let dir = match quarantine_dir() {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+
+    let name = format!(
+        "{}-{}.{}",
+        path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str()).unwrap_or("cache"),
+        path.file_name().and_then(|n| n.to_str()).unwrap_or("cache"),
+        Local::now().format("%Y%m%d%H%M%S")
+    );
+
+    if fs::create_dir_all(&dir).is_err() || fs::rename(path, dir.join(name)).is_err() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn split(file : CacheFile) -> (FetchTimes, HashMap<Date<Local>, Vec<String>>) {
+    let fetched = file.fetched
+        .into_iter()
+        .filter_map(|(k,at)| Local.from_local_date(&k).single().map(|d| (d, at)))
+        .collect();
+
+    let days = file.days
+        .into_iter()
+        .filter_map(|(k,v)| Local.from_local_date(&k).single().map(|d| (d, v)))
+        .collect();
+
+    (fetched, days)
+}
+
+/// Moves a version 1 cache of `course` into the current layout.
+fn migrate_legacy(course : &str) -> Result<(), String> {
+    let legacy = legacy_path(course)?;
+    if !legacy.exists() {
+        return Ok(());
+    }
+
+    let _lock = lock(true)?;
+
+    // Someone else might have been quicker.
+    let contents = match fs::read_to_string(&legacy) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(()),
+    };
+
+    let data : AppDataStorage = match serde_json::from_str(&contents) {
+        Ok(data) => data,
+        Err(_) => {
+            quarantine(&legacy);
+            return Ok(());
+        }
+    };
+
+    // Only where there is nothing better, a newer file stays as it is.
+    let timetable_path = cache_path(course)?;
+    if matches!(read_file(&timetable_path), Read::Missing | Read::Corrupt) {
+        let days = data.timetable.iter().map(|(k,v)| (k.date(), v.clone())).collect();
+        write_merged(&timetable_path, &days, &legacy_fetched(&days, &legacy))?;
+    }
+
+    let canteen_path = canteen_path()?;
+    if matches!(read_file(&canteen_path), Read::Missing | Read::Corrupt) {
+        let days = data.canteen.iter().map(|(k,v)| (k.date(), v.clone())).collect();
+        write_merged(&canteen_path, &days, &legacy_fetched(&days, &legacy))?;
+    }
+
+    fs::remove_file(&legacy).map_err(|e| e.to_string())
+}
+
+/// Version 1 didn't know when weeks were fetched, every week counts as fetched when the file was written.
+fn legacy_fetched(days : &HashMap<Date<Local>, Vec<String>>, path : &Path) -> FetchTimes {
+    let modified = modified(path);
+
+    days.keys().map(|d| (monday_of(d), modified)).collect()
+}
+
+fn modified(path : &Path) -> DateTime<Local> {
+    path.metadata()
+        .and_then(|m| m.modified())
+        .map(DateTime::<Local>::from)
+        .unwrap_or_else(|_| Local::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Once;
+
+    /// All tests share one cache below the temp dir, each with its own course.
+    fn setup() {
+        static SETUP: Once = Once::new();
+
+        SETUP.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("hs_app-cache-test-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            std::env::set_var("XDG_CACHE_HOME", &dir);
+        });
+    }
+
+    fn monday() -> Date<Local> {
+        monday_of(&Local::today())
+    }
+
+    fn data(cell: &str) -> AppData {
+        let mut data = AppData::default();
+        data.timetable.insert(monday(), vec![cell.to_string()]);
+        data.timetable_fetched.insert(monday(), Local::now());
+        data
+    }
+
+    #[test]
+    fn written_data_reads_back() {
+        setup();
+        write_cache(&data("Mathematik 2"), "ROUNDTRIP").unwrap();
+
+        let read = read_cache("ROUNDTRIP").unwrap().unwrap();
+        assert_eq!(read.timetable.get(&monday()), Some(&vec!["Mathematik 2".to_string()]));
+        assert!(read.timetable_fetched.contains_key(&monday()));
+    }
+
+    #[test]
+    fn missing_timetable_is_no_cache() {
+        setup();
+        assert!(read_cache("NOTHING").unwrap().is_none());
+    }
+
+    #[test]
+    fn nothing_to_write_leaves_no_file() {
+        setup();
+        write_cache(&AppData::default(), "EMPTY").unwrap();
+
+        assert!(!cache_path("EMPTY").unwrap().exists());
+    }
+
+    #[test]
+    fn newer_files_are_neither_read_nor_overwritten() {
+        setup();
+        let path = cache_path("NEWER").unwrap();
+        let newer = format!("{{\"version\":{},\"future\":true}}", CACHE_VERSION + 1);
+        write_atomic(&path, &newer).unwrap();
+
+        assert!(read_cache("NEWER").unwrap().is_none());
+
+        let err = write_cache(&data("Mathematik 2"), "NEWER").unwrap_err();
+        assert!(err.contains("newer"), "{}", err);
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+    }
+
+    #[test]
+    fn corrupt_files_are_quarantined() {
+        setup();
+        let path = cache_path("CORRUPT").unwrap();
+        write_atomic(&path, "{\"version\":2,\"days\":").unwrap();
+
+        assert!(read_cache("CORRUPT").unwrap().is_none());
+        assert!(!path.exists());
+        assert!(quarantined()
+            .iter()
+            .any(|p| p.to_string_lossy().contains("timetable-CORRUPT.json")));
+    }
+
+    #[test]
+    fn files_without_version_are_corrupt() {
+        setup();
+        let path = cache_path("UNVERSIONED").unwrap();
+        write_atomic(&path, "{\"fetched\":{},\"days\":{}}").unwrap();
+
+        assert!(matches!(read_file(&path), Read::Corrupt));
+    }
+
+    #[test]
+    fn legacy_files_are_migrated() {
+        setup();
+        let day = Local::today().and_hms(0, 0, 0);
+        let mut timetable = HashMap::new();
+        timetable.insert(day, vec!["Datenbanken".to_string()]);
+
+        let legacy = legacy_path("LEGACY").unwrap();
+        let storage = AppDataStorage { canteen: HashMap::new(), timetable };
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, serde_json::to_string(&storage).unwrap()).unwrap();
+
+        let read = read_cache("LEGACY").unwrap().unwrap();
+        assert_eq!(read.timetable.get(&day.date()), Some(&vec!["Datenbanken".to_string()]));
+        // Version 1 only knew when the file was written.
+        assert!(read.timetable_fetched.contains_key(&monday_of(&day.date())));
+        assert!(!legacy.exists());
+    }
 }
@@ -0,0 +1,418 @@
+use hs_crawler::timetable::{self, Lecture};
+
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::fetch::{self, Selection, Week};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Acknowledged changes and changes of past days are dropped after this.
+const KEEP_DAYS: i64 = 14;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ChangeKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        }
+    }
+}
+
+/// One slot of one day whose timetable entry differs from what was known before.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Change {
+    pub course: String,
+    pub date: NaiveDate,
+    pub slot: usize,
+    pub kind: ChangeKind,
+    /// The cells as crawled, `old` is empty for added ones and `new` for removed ones.
+    pub old: String,
+    pub new: String,
+    pub detected_at: DateTime<Local>,
+    /// Seen in the TUI or acknowledged with `changes --ack`.
+    pub acked: bool,
+    /// `changes --exec` already ran for it.
+    pub notified: bool,
+}
+
+impl Change {
+    /// Like "21.10.2026 09:45 INFM2 changed: Mathematik 2 (B104) → Mathematik 2 (E101)".
+    pub fn describe(&self) -> String {
+        let time = match timetable::slot_time(self.slot) {
+            Some((start, _)) => start.format("%H:%M").to_string(),
+            None => format!("{}.", self.slot + 1),
+        };
+
+        let what = match self.kind {
+            ChangeKind::Added => summary(&self.course, self.slot, &self.new),
+            ChangeKind::Removed => summary(&self.course, self.slot, &self.old),
+            ChangeKind::Changed => format!(
+                "{} → {}",
+                summary(&self.course, self.slot, &self.old),
+                summary(&self.course, self.slot, &self.new)
+            ),
+        };
+
+        format!("{} {} {} {}: {}", self.date.format("%d.%m.%Y"), time, self.course, self.kind.label(), what)
+    }
+
+    fn is_same_slot(&self, other: &Change) -> bool {
+        self.course == other.course && self.date == other.date && self.slot == other.slot
+    }
+}
+
+fn summary(course: &str, slot: usize, text: &str) -> String {
+    match Lecture::parse(course, slot, text) {
+        Some(Lecture { title, room: Some(room), .. }) => format!("{} ({})", title, room),
+        Some(l) => l.title,
+        None => String::new(),
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("changes")
+        .about("Fetches the timetable and lists lectures that were added, removed or moved.")
+        .arg(Arg::with_name("all")
+            .long("all")
+            .help("Also lists acknowledged changes.")
+        ).arg(Arg::with_name("ack")
+            .long("ack")
+            .help("Acknowledges the listed changes.")
+        ).arg(Arg::with_name("exec")
+            .short("e")
+            .long("exec")
+            .takes_value(true)
+            .help("Shell command to run once per new change, details are in HS_* environment variables.")
+        )
+}
+
+fn changes_path() -> Result<PathBuf, String> {
+    Ok(cache::cache_dir()?.join("changes.json"))
+}
+
+/// What changed from `previous` to `week` for `course`. Days `previous`
+/// doesn't know and days in the past don't count.
+pub fn detect(course: &str, week: &Week, previous: &Week) -> Vec<Change> {
+    let today = Local::today();
+    let now = Local::now();
+    let mut changes = vec![];
+
+    for (date, day) in week.iter() {
+        let old_day = match previous.get(date) {
+            Some(old_day) if *date >= today => old_day,
+            _ => continue,
+        };
+
+        for slot in 0..day.len().max(old_day.len()) {
+            let old = old_day.get(slot).map(|t| t.trim()).unwrap_or("");
+            let new = day.get(slot).map(|t| t.trim()).unwrap_or("");
+
+            let kind = match (old.is_empty(), new.is_empty()) {
+                _ if old == new => continue,
+                (true, false) => ChangeKind::Added,
+                (false, true) => ChangeKind::Removed,
+                _ => ChangeKind::Changed,
+            };
+
+            changes.push(Change {
+                course: course.to_string(),
+                date: date.naive_local(),
+                slot,
+                kind,
+                old: old.to_string(),
+                new: new.to_string(),
+                detected_at: now,
+                acked: false,
+                notified: false,
+            });
+        }
+    }
+
+    changes
+}
+
+pub fn load() -> Vec<Change> {
+    changes_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save(changes: &[Change]) -> Result<(), String> {
+    let out = serde_json::to_string(changes).map_err(|e| e.to_string())?;
+    cache::write_atomic(&changes_path()?, &out)
+}
+
+/// Changes `changes` under the cache lock and saves them.
+fn update<F: FnOnce(&mut Vec<Change>)>(f: F) -> Result<Vec<Change>, String> {
+    let _lock = cache::lock(true)?;
+
+    let mut changes = load();
+    f(&mut changes);
+
+    let today = Local::today().naive_local();
+    changes.retain(|c| {
+        let past = today.signed_duration_since(c.date).num_days() > 0;
+        let old = Local::now().signed_duration_since(c.detected_at).num_days() > KEEP_DAYS;
+        !(old && (past || c.acked))
+    });
+
+    save(&changes)?;
+    Ok(changes)
+}
+
+/// Adds newly detected changes. A slot that changed again keeps what it was
+/// originally, and is dropped if it is back to that.
+pub fn record(detected: Vec<Change>) -> Result<Vec<Change>, String> {
+    update(|changes| merge(changes, detected))
+}
+
+fn merge(changes: &mut Vec<Change>, detected: Vec<Change>) {
+    for mut change in detected {
+        // Another instance might have compared against an older cache. Only
+        // the latest entry counts, the slot may have changed back and forth.
+        let latest = changes.iter().rev().find(|c| c.is_same_slot(&change));
+        if latest.map(|c| c.new == change.new).unwrap_or(false) {
+            continue;
+        }
+
+        let known = changes.iter().position(|c| !c.acked && c.is_same_slot(&change));
+
+        if let Some(i) = known {
+            let original = changes.remove(i);
+            if original.old == change.new {
+                continue;
+            }
+
+            change.old = original.old;
+            change.kind = match (change.old.is_empty(), change.new.is_empty()) {
+                (true, _) => ChangeKind::Added,
+                (_, true) => ChangeKind::Removed,
+                _ => ChangeKind::Changed,
+            };
+        }
+
+        changes.push(change);
+    }
+}
+
+/// Acknowledges all changes of `courses`.
+pub fn ack(courses: &[String]) -> Result<Vec<Change>, String> {
+    update(|changes| {
+        changes
+            .iter_mut()
+            .filter(|c| courses.contains(&c.course))
+            .for_each(|c| c.acked = true)
+    })
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    // Detects changes on the way, offline it only lists what is known.
+    let fetched = fetch::fetch_cached(
+        &Selection {
+            timetable: true,
+            canteen: false,
+            ..Selection::default()
+        },
+        &config.courses,
+    );
+
+    for e in fetched.errors.iter() {
+        eprintln!("Warning: {}", e);
+    }
+
+    let all = matches.is_present("all");
+    let mut changes = load()
+        .into_iter()
+        .filter(|c| config.courses.contains(&c.course))
+        .filter(|c| all || !c.acked)
+        .collect::<Vec<Change>>();
+    changes.sort_by(|a, b| (a.date, a.slot, &a.course).cmp(&(b.date, b.slot, &b.course)));
+
+    if changes.is_empty() {
+        println!("No changes.");
+    }
+
+    for c in changes.iter() {
+        println!("{}", c.describe());
+    }
+
+    if let Some(exec) = matches.value_of("exec") {
+        let mut notified = vec![];
+
+        for c in changes.iter().filter(|c| !c.notified) {
+            if notify(exec, c) {
+                notified.push(c.clone());
+            }
+        }
+
+        update(|known| {
+            for c in known.iter_mut() {
+                if notified.iter().any(|n| n.is_same_slot(c) && n.new == c.new) {
+                    c.notified = true;
+                }
+            }
+        })?;
+    }
+
+    if matches.is_present("ack") {
+        ack(&config.courses)?;
+    }
+
+    Ok(())
+}
+
+/// Runs the hook for `change`, true if it succeeded.
+fn notify(exec: &str, change: &Change) -> bool {
+    let result = Command::new("sh")
+        .arg("-c")
+        .arg(exec)
+        .env("HS_KIND", change.kind.label())
+        .env("HS_COURSE", &change.course)
+        .env("HS_DATE", change.date.format("%Y-%m-%d").to_string())
+        .env("HS_SLOT", (change.slot + 1).to_string())
+        .env("HS_OLD", &change.old)
+        .env("HS_NEW", &change.new)
+        .env("HS_TEXT", change.describe())
+        .status();
+
+    match result {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            eprintln!("'{}' exited with {}", exec, status);
+            false
+        }
+        Err(e) => {
+            eprintln!("Can't run '{}': {}", exec, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn week(days: &[(i64, &[&str])]) -> Week {
+        days.iter()
+            .map(|(offset, slots)| {
+                (Local::today() + chrono::Duration::days(*offset), slots.iter().map(|s| s.to_string()).collect())
+            })
+            .collect()
+    }
+
+    fn change(slot: usize, old: &str, new: &str) -> Change {
+        let mut old_day = vec![""; slot + 1];
+        let mut new_day = vec![""; slot + 1];
+        old_day[slot] = old;
+        new_day[slot] = new;
+
+        let mut changes = detect("AI3", &week(&[(1, &new_day)]), &week(&[(1, &old_day)]));
+        assert_eq!(changes.len(), 1);
+        changes.remove(0)
+    }
+
+    #[test]
+    fn detects_added_removed_and_changed_slots() {
+        let previous = week(&[(1, &["Mathe\nB104", "Physik", ""])]);
+        let current = week(&[(1, &["Mathe\nE101", "", "Labor"])]);
+
+        let changes = detect("AI3", &current, &previous);
+        let kinds = changes.iter().map(|c| (c.slot, c.kind)).collect::<Vec<_>>();
+
+        assert_eq!(kinds, vec![
+            (0, ChangeKind::Changed),
+            (1, ChangeKind::Removed),
+            (2, ChangeKind::Added),
+        ]);
+        assert_eq!(changes[0].old, "Mathe\nB104");
+        assert_eq!(changes[0].new, "Mathe\nE101");
+    }
+
+    #[test]
+    fn ignores_past_and_unknown_days() {
+        let previous = week(&[(-1, &["Mathe"]), (1, &["Mathe"])]);
+        let current = week(&[(-1, &["Physik"]), (1, &["Mathe"]), (2, &["Physik"])]);
+
+        assert!(detect("AI3", &current, &previous).is_empty());
+    }
+
+    #[test]
+    fn ignores_whitespace() {
+        let previous = week(&[(1, &["Mathe\n"])]);
+        let current = week(&[(1, &["  Mathe"])]);
+
+        assert!(detect("AI3", &current, &previous).is_empty());
+    }
+
+    #[test]
+    fn skips_changes_seen_before() {
+        let mut changes = vec![];
+        merge(&mut changes, vec![change(0, "A", "B")]);
+        merge(&mut changes, vec![change(0, "A", "B")]);
+
+        assert_eq!(changes.len(), 1);
+    }
+
+    #[test]
+    fn keeps_the_original_of_a_slot_that_changes_again() {
+        let mut changes = vec![];
+        merge(&mut changes, vec![change(0, "A", "B")]);
+        merge(&mut changes, vec![change(0, "B", "C")]);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].old.as_str(), changes[0].new.as_str()), ("A", "C"));
+        assert_eq!(changes[0].kind, ChangeKind::Changed);
+    }
+
+    #[test]
+    fn drops_a_slot_that_is_back_to_its_original() {
+        let mut changes = vec![];
+        merge(&mut changes, vec![change(0, "A", "B")]);
+        merge(&mut changes, vec![change(0, "B", "A")]);
+
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn kind_follows_the_original() {
+        let mut changes = vec![];
+        merge(&mut changes, vec![change(0, "", "B")]);
+        merge(&mut changes, vec![change(0, "B", "C")]);
+
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+        assert_eq!(changes[0].old, "");
+    }
+
+    #[test]
+    fn reports_a_change_again_after_it_was_acked_and_reverted() {
+        let mut changes = vec![];
+        merge(&mut changes, vec![change(0, "A", "B")]);
+        changes.iter_mut().for_each(|c| c.acked = true);
+
+        merge(&mut changes, vec![change(0, "B", "A")]);
+        changes.iter_mut().for_each(|c| c.acked = true);
+
+        merge(&mut changes, vec![change(0, "A", "B")]);
+
+        let unacked = changes.iter().filter(|c| !c.acked).collect::<Vec<_>>();
+        assert_eq!(unacked.len(), 1);
+        assert_eq!((unacked[0].old.as_str(), unacked[0].new.as_str()), ("A", "B"));
+    }
+}
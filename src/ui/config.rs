@@ -0,0 +1,277 @@
+use crate::ui::cache;
+use crate::ui::diet::Diet;
+use crate::ui::fetch;
+use crate::ui::filter::Filter;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use hs_crawler::DEFAULT_CANTEEN;
+
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Overrides the canteen of the config file, e.g. `mensa-rempartstrasse`.
+pub const CANTEEN_ENV: &str = "HS_CRAWLER_CANTEEN";
+
+/// The canteen in use, for the cache and history files named after it.
+static CANTEEN: RwLock<String> = RwLock::new(String::new());
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The first one is used where only one course fits.
+    pub courses: Vec<String>,
+    /// SWFR canteen as in its url, like "mensa-offenburg".
+    pub canteen: String,
+    /// "auto", "truecolor" or "simple".
+    pub theme: String,
+    /// After this hour the TUI and `today` show the next day.
+    pub rollover_hour: u32,
+    /// Show monday instead of saturday and sunday.
+    pub skip_weekend: bool,
+    /// Minutes between refreshes of long running modes, 0 turns them off in the TUI.
+    pub refresh_minutes: u64,
+    pub keys: KeyBindings,
+    /// Lectures to hide, applied to the TUI, plain output and exports.
+    pub filter: Filter,
+    /// Meals to prefer and allergens to avoid.
+    pub diet: Diet,
+    /// Keep every fetched week for `hs_app history`.
+    pub history: bool,
+    /// Start the TUI on the day it was left on, if that isn't over yet.
+    pub restore_day: bool,
+}
+
+/// Every character of a binding triggers it. Arrow keys, Enter and Ctrl-C always work.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub next_day: String,
+    pub prev_day: String,
+    pub mode: String,
+    pub hide_warnings: String,
+    pub quit: String,
+    /// Selects the next/previous lecture of the day.
+    pub select_next: String,
+    pub select_prev: String,
+    /// Hides the selected lecture for good, `unhide` takes back the last one.
+    pub hide: String,
+    pub unhide: String,
+    /// Acknowledges highlighted timetable changes.
+    pub ack_changes: String,
+    /// Fetches everything again.
+    pub refresh: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            courses: vec!["INFM2".to_string()],
+            canteen: DEFAULT_CANTEEN.to_string(),
+            theme: "auto".to_string(),
+            rollover_hour: 18,
+            skip_weekend: true,
+            refresh_minutes: 60,
+            keys: KeyBindings::default(),
+            filter: Filter::default(),
+            diet: Diet::default(),
+            history: false,
+            restore_day: false,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// How `binding` is shown in the help, its first character.
+    pub fn label(binding: &str) -> String {
+        binding.chars().next().map(|c| c.to_string()).unwrap_or_else(|| "-".to_string())
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            next_day: "lL".to_string(),
+            prev_day: "hH".to_string(),
+            mode: "mM".to_string(),
+            hide_warnings: "wW".to_string(),
+            quit: "qQ".to_string(),
+            select_next: "jJ".to_string(),
+            select_prev: "kK".to_string(),
+            hide: "xX".to_string(),
+            unhide: "uU".to_string(),
+            ack_changes: "aA".to_string(),
+            refresh: "rR".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// The course for everything that shows only one.
+    pub fn course(&self) -> &str {
+        &self.courses[0]
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.courses.is_empty() {
+            return Err("courses must not be empty.".to_string());
+        }
+
+        if let Some(c) = self.courses.iter().find(|c| !fetch::is_valid_course(c)) {
+            return Err(format!("Invalid course name '{}'.", c));
+        }
+
+        if !["auto", "truecolor", "simple"].contains(&self.theme.as_str()) {
+            return Err(format!("Unknown theme '{}', use auto, truecolor or simple.", self.theme));
+        }
+
+        if self.rollover_hour > 23 {
+            return Err("rollover_hour must be between 0 and 23.".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("config")
+        .about("Prints the effective config, i.e. the config file with command line overrides.")
+}
+
+/// `--config`, shared by every mode.
+pub fn arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("config")
+        .long("config")
+        .takes_value(true)
+        .value_name("FILE")
+        .global(true)
+        .help("Config file to use instead of the default one.")
+}
+
+pub fn default_path() -> Result<PathBuf, String> {
+    let mut path = dirs::config_dir().ok_or("Unable to find config dir.")?;
+    path.push("hs_app");
+    path.push("config.json");
+
+    Ok(path)
+}
+
+/// Reads the config file, or the defaults if there is none.
+pub fn load(path: &Path) -> Result<Config, String> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let config: Config = serde_json::from_reader(&file)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    config.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(config)
+}
+
+/// The config file with the command line on top.
+pub fn from_matches(matches: &ArgMatches) -> Result<(Config, PathBuf), String> {
+    let sub = matches.subcommand().1;
+    let value_of = |name: &str| sub.and_then(|s| s.value_of(name)).or_else(|| matches.value_of(name));
+
+    let path = match value_of("config") {
+        Some(path) => PathBuf::from(path),
+        None => default_path()?,
+    };
+
+    let mut config = load(&path)?;
+
+    let courses = sub
+        .and_then(|s| s.values_of("course"))
+        .or_else(|| matches.values_of("course"));
+
+    if let Some(courses) = courses {
+        config.courses = courses.map(|c| c.to_uppercase()).collect();
+    }
+
+    if matches.is_present("simplecolor") {
+        config.theme = "simple".to_string();
+    }
+
+    if let Some(canteen) = canteen_from_env() {
+        config.canteen = canteen;
+    }
+
+    config.courses = config.courses.iter().map(|c| c.to_uppercase()).collect();
+    config.validate()?;
+
+    Ok((config, path))
+}
+
+/// `CANTEEN_ENV`, if it is set to a canteen.
+pub fn canteen_from_env() -> Option<String> {
+    env::var(CANTEEN_ENV)
+        .ok()
+        .map(|name| name.trim().trim_matches('/').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Set once the canteen of the config is known, before anything is fetched.
+pub fn set_canteen(canteen: &str) {
+    *CANTEEN.write().unwrap() = canteen.to_string();
+}
+
+/// See `set_canteen`, `DEFAULT_CANTEEN` until then.
+pub fn canteen() -> String {
+    let canteen = CANTEEN.read().unwrap();
+
+    if canteen.is_empty() {
+        DEFAULT_CANTEEN.to_string()
+    } else {
+        canteen.clone()
+    }
+}
+
+/// Whether the config file at `path` sets `key` itself, rather than using the default.
+pub fn is_set(path: &Path, key: &str) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .map(|file| file.get(key).is_some())
+        .unwrap_or(false)
+}
+
+/// Writes `filter` into the config file at `path`, leaving everything else as it is.
+pub fn save_filter(path: &Path, filter: &Filter) -> Result<(), String> {
+    let mut file: serde_json::Value = if path.exists() {
+        let f = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_reader(&f).map_err(|e| format!("{}: {}", path.display(), e))?
+    } else {
+        serde_json::Value::Object(Default::default())
+    };
+
+    let object = file
+        .as_object_mut()
+        .ok_or_else(|| format!("{}: Expected an object.", path.display()))?;
+    object.insert(
+        "filter".to_string(),
+        serde_json::to_value(filter).map_err(|e| e.to_string())?,
+    );
+
+    // A crash while writing must not cost the hand-written config.
+    let out = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    cache::write_atomic(path, &(out + "\n"))
+}
+
+pub fn print(config: &Config, path: &Path) -> Result<(), String> {
+    if path.exists() {
+        eprintln!("Config file: {}", path.display());
+    } else {
+        eprintln!("No config file at {}, using defaults.", path.display());
+    }
+
+    let out = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    println!("{}", out);
+
+    Ok(())
+}
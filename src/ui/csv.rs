@@ -0,0 +1,114 @@
+use hs_crawler::canteen_plan;
+
+use crate::ui::fetch::{Fetched, Selection};
+use crate::ui::json::iso_date;
+use crate::ui::merge;
+
+use chrono::prelude::*;
+
+use std::collections::HashMap;
+
+const HEADER: [&str; 11] = [
+    "source", "date", "weekday", "slot", "start", "end", "course", "title", "lecturer", "room", "meal",
+];
+
+/// One row per lecture and meal, see `HEADER`. Overlapping lectures of
+/// several courses get a row each.
+pub fn render(fetched: &Fetched, selection: &Selection) -> String {
+    let mut out = String::new();
+    push_row(&mut out, &HEADER.iter().map(|s| s.to_string()).collect::<Vec<String>>());
+
+    if selection.timetable {
+        for date in merge::days(&fetched.timetable).iter() {
+            for l in merge::lectures(&fetched.timetable, date).unwrap_or_default() {
+                push_row(&mut out, &[
+                    "timetable".to_string(),
+                    iso_date(date),
+                    crate::german_weekday(date.weekday()).to_string(),
+                    l.slot.to_string(),
+                    l.start.map(|t| t.format("%H:%M").to_string()).unwrap_or_default(),
+                    l.end.map(|t| t.format("%H:%M").to_string()).unwrap_or_default(),
+                    l.course,
+                    l.title,
+                    l.lecturer.unwrap_or_default(),
+                    l.room.unwrap_or_default(),
+                    String::new(),
+                ]);
+            }
+        }
+    }
+
+    if selection.canteen {
+        for (date, day) in sorted(&fetched.canteen) {
+            for (i, m) in canteen_plan::meals(day).into_iter().enumerate() {
+                push_row(&mut out, &[
+                    "canteen".to_string(),
+                    iso_date(date),
+                    crate::german_weekday(date.weekday()).to_string(),
+                    i.to_string(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    m.name.unwrap_or_default(),
+                    String::new(),
+                    String::new(),
+                    m.description,
+                ]);
+            }
+        }
+    }
+
+    out
+}
+
+fn push_row(out: &mut String, fields: &[String]) {
+    let row = fields.iter().map(|f| quote(f)).collect::<Vec<String>>().join(",");
+
+    out.push_str(&row);
+    out.push_str("\r\n");
+}
+
+/// RFC 4180 quoting, only where needed.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn sorted(data: &HashMap<Date<Local>, Vec<String>>) -> Vec<(&Date<Local>, &Vec<String>)> {
+    let mut days = data.iter().collect::<Vec<_>>();
+    days.sort_by_key(|(d, _)| **d);
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_fields_stay_unquoted() {
+        assert_eq!(quote("Mathematik 2"), "Mathematik 2");
+        assert_eq!(quote(""), "");
+    }
+
+    #[test]
+    fn separators_and_line_breaks_are_quoted() {
+        assert_eq!(quote("B104, B105"), "\"B104, B105\"");
+        assert_eq!(quote("Salat\nBuffet"), "\"Salat\nBuffet\"");
+        assert_eq!(quote("a\rb"), "\"a\rb\"");
+    }
+
+    #[test]
+    fn quotes_are_doubled() {
+        assert_eq!(quote("Pasta \"Napoli\""), "\"Pasta \"\"Napoli\"\"\"");
+    }
+
+    #[test]
+    fn rows_end_with_crlf() {
+        let mut out = String::new();
+        push_row(&mut out, &["a".to_string(), "b,c".to_string()]);
+        assert_eq!(out, "a,\"b,c\"\r\n");
+    }
+}
@@ -0,0 +1,188 @@
+use hs_crawler::canteen_plan::Meal;
+
+/// What to look out for in the canteen plan.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Diet {
+    /// Labels like "vegan" or "vegetarisch", the first one matters most.
+    pub prefer: Vec<String>,
+    /// Allergens by name or letter, like "Erdnüsse" or "E".
+    pub avoid: Vec<String>,
+    /// Leave out meals with avoided allergens instead of marking them.
+    pub hide_avoided: bool,
+}
+
+impl Diet {
+    pub fn is_empty(&self) -> bool {
+        self.prefer.is_empty() && self.avoid.is_empty()
+    }
+
+    /// The allergens of `meal` that are avoided.
+    pub fn conflicts<'a>(&self, meal: &'a Meal) -> Vec<&'a str> {
+        meal.allergens
+            .iter()
+            .filter(|a| self.avoid.iter().any(|rule| allergen_matches(rule, a)))
+            .map(|a| a.as_str())
+            .collect()
+    }
+
+    pub fn is_avoided(&self, meal: &Meal) -> bool {
+        !self.conflicts(meal).is_empty()
+    }
+
+    /// Position of the first preferred label of `meal`, lower is better.
+    fn rank(&self, meal: &Meal) -> Option<usize> {
+        self.prefer
+            .iter()
+            .position(|p| meal.labels.iter().any(|l| l.eq_ignore_ascii_case(p.trim())))
+    }
+
+    pub fn is_preferred(&self, meal: &Meal) -> bool {
+        !self.is_avoided(meal) && self.rank(meal).is_some()
+    }
+
+    /// The meals to show, without the avoided ones if they are hidden.
+    pub fn shown(&self, meals: Vec<Meal>) -> Vec<Meal> {
+        if !self.hide_avoided {
+            return meals;
+        }
+
+        meals.into_iter().filter(|m| !self.is_avoided(m)).collect()
+    }
+
+    /// The best preferred meal without avoided allergens. Without preferences
+    /// the first safe one, and nothing without a profile at all.
+    pub fn best_pick<'a>(&self, meals: &'a [Meal]) -> Option<&'a Meal> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let safe = meals.iter().filter(|m| !self.is_avoided(m));
+
+        if self.prefer.is_empty() {
+            return safe.into_iter().next();
+        }
+
+        safe.filter_map(|m| self.rank(m).map(|r| (r, m)))
+            .min_by_key(|(r, _)| *r)
+            .map(|(_, m)| m)
+    }
+
+    /// A short marker to put in front of `meal`.
+    pub fn mark(&self, meal: &Meal) -> &'static str {
+        if self.is_avoided(meal) {
+            "⚠ "
+        } else if self.is_preferred(meal) {
+            "★ "
+        } else {
+            ""
+        }
+    }
+}
+
+/// `rule` is either the letter of an allergen or part of its name,
+/// the crawler gives them as "Erdnüsse (E)".
+fn allergen_matches(rule: &str, allergen: &str) -> bool {
+    let rule = rule.trim().to_lowercase();
+    if rule.is_empty() {
+        return false;
+    }
+
+    let (name, code) = match allergen.find('(') {
+        Some(i) => (&allergen[..i], allergen[i + 1..].trim_end_matches(')')),
+        None => (allergen, ""),
+    };
+
+    // A single letter would be part of most names.
+    if rule.chars().count() == 1 {
+        code.trim().to_lowercase() == rule
+    } else {
+        name.trim().to_lowercase().contains(&rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meal(description: &str, allergens: &[&str], labels: &[&str]) -> Meal {
+        Meal {
+            name: None,
+            description: description.to_string(),
+            allergens: allergens.iter().map(|s| s.to_string()).collect(),
+            labels: labels.iter().map(|s| s.to_string()).collect(),
+            text: description.to_string(),
+        }
+    }
+
+    fn diet(prefer: &[&str], avoid: &[&str]) -> Diet {
+        Diet {
+            prefer: prefer.iter().map(|s| s.to_string()).collect(),
+            avoid: avoid.iter().map(|s| s.to_string()).collect(),
+            hide_avoided: false,
+        }
+    }
+
+    fn meals() -> Vec<Meal> {
+        vec![
+            meal("Schnitzel", &["Gluten (A)", "Eier (C)"], &["Schwein"]),
+            meal("Erdnuss-Curry", &["Erdnüsse (E)"], &["vegan"]),
+            meal("Käsespätzle", &["Gluten (A)", "Milch (G)"], &["vegetarisch"]),
+            meal("Linsen-Dal", &[], &["Vegan"]),
+        ]
+    }
+
+    #[test]
+    fn allergens_match_by_name_or_letter() {
+        let meals = meals();
+
+        assert_eq!(diet(&[], &["erdnüsse"]).conflicts(&meals[1]), vec!["Erdnüsse (E)"]);
+        assert_eq!(diet(&[], &["E"]).conflicts(&meals[1]), vec!["Erdnüsse (E)"]);
+        assert_eq!(diet(&[], &["glu", "g"]).conflicts(&meals[2]), vec!["Gluten (A)", "Milch (G)"]);
+
+        // A single letter is a code, not part of a name.
+        assert!(!diet(&[], &["e"]).is_avoided(&meals[0]));
+        assert!(!diet(&[], &[" "]).is_avoided(&meals[0]));
+    }
+
+    #[test]
+    fn best_pick_follows_the_preference_order() {
+        let meals = meals();
+
+        let pick = diet(&["vegan", "vegetarisch"], &[]).best_pick(&meals);
+        assert_eq!(pick.map(|m| m.description.as_str()), Some("Erdnuss-Curry"));
+
+        let pick = diet(&["vegetarisch", "vegan"], &[]).best_pick(&meals);
+        assert_eq!(pick.map(|m| m.description.as_str()), Some("Käsespätzle"));
+    }
+
+    #[test]
+    fn best_pick_skips_avoided_meals() {
+        let meals = meals();
+
+        let pick = diet(&["vegan"], &["E"]).best_pick(&meals);
+        assert_eq!(pick.map(|m| m.description.as_str()), Some("Linsen-Dal"));
+
+        let pick = diet(&[], &["Gluten"]).best_pick(&meals);
+        assert_eq!(pick.map(|m| m.description.as_str()), Some("Erdnuss-Curry"));
+
+        assert_eq!(diet(&["halal"], &[]).best_pick(&meals), None);
+        assert_eq!(Diet::default().best_pick(&meals), None);
+    }
+
+    #[test]
+    fn avoided_meals_are_marked_or_hidden() {
+        let mut diet = diet(&["vegan"], &["E"]);
+        let meals = meals();
+
+        assert_eq!(diet.mark(&meals[0]), "");
+        assert_eq!(diet.mark(&meals[1]), "⚠ ");
+        assert_eq!(diet.mark(&meals[3]), "★ ");
+        assert_eq!(diet.shown(meals.clone()).len(), 4);
+
+        diet.hide_avoided = true;
+        let shown = diet.shown(meals);
+        assert_eq!(shown.len(), 3);
+        assert!(shown.iter().all(|m| m.description != "Erdnuss-Curry"));
+    }
+}
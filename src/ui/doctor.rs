@@ -0,0 +1,229 @@
+use hs_crawler;
+use hs_crawler::report::Report;
+
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::sanity;
+
+use chrono::prelude::*;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Health {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl Health {
+    fn label(self) -> &'static str {
+        match self {
+            Health::Ok => "[ OK ]",
+            Health::Warn => "[WARN]",
+            Health::Fail => "[FAIL]",
+        }
+    }
+}
+
+/// Checks every data source for the configured courses and prints a report.
+/// Returns `Err` if any check failed.
+pub fn run(config: &Config) -> Result<(), String> {
+    let mut results = vec![];
+
+    println!("HS APP doctor, courses {}\n", config.courses.join(", "));
+
+    // The index is the same for every course, so it is only fetched once.
+    let (index, report) = hs_crawler::timetable::diagnose_index();
+    let health = match index {
+        Ok(ref index) => {
+            let mut missing = config
+                .courses
+                .iter()
+                .filter(|c| !index.contains_key(&c.to_lowercase()))
+                .peekable();
+
+            if missing.peek().is_none() {
+                print_check(Health::Ok, "Course index", &format!("{} courses, all found", index.len()));
+                Health::Ok
+            } else {
+                let missing = missing.cloned().collect::<Vec<String>>();
+                let mut known = index.keys().map(|k| k.to_uppercase()).collect::<Vec<String>>();
+                known.sort();
+                print_check(
+                    Health::Fail,
+                    "Course index",
+                    &format!("{} not found, known: {}", missing.join(", "), known.join(", ")),
+                );
+                Health::Fail
+            }
+        }
+        Err(ref e) => {
+            print_check(Health::Fail, "Course index", e);
+            Health::Fail
+        }
+    };
+    print_report(&report);
+    results.push(health);
+
+    let cached = config
+        .courses
+        .iter()
+        .map(|course| (course, cache::read_cache(course).ok().flatten()))
+        .collect::<Vec<_>>();
+
+    if let Ok(ref index) = index {
+        for (course, cached) in cached.iter() {
+            for (name, q) in [
+                ("this week", hs_crawler::timetable::Query::ThisWeek),
+                ("next week", hs_crawler::timetable::Query::NextWeek),
+            ] {
+                let (res, report) = hs_crawler::timetable::diagnose(q, course, index);
+                let warnings = match (&res, cached) {
+                    (Ok(week), Some(cached)) => sanity::check_timetable(week, &cached.timetable),
+                    (Ok(week), None) => sanity::check_timetable(week, &Default::default()),
+                    _ => vec![],
+                };
+                results.push(check_week(&format!("Timetable {}, {}", course, name), &res, &report, &warnings));
+            }
+        }
+    }
+
+    let cached_canteen = cached.iter().find_map(|(_, c)| c.as_ref());
+    for (name, q) in [
+        ("this week", hs_crawler::canteen_plan::Query::ThisWeek),
+        ("next week", hs_crawler::canteen_plan::Query::NextWeek),
+    ] {
+        let (res, report) = hs_crawler::canteen_plan::diagnose(q, &config.canteen);
+        let warnings = match (&res, cached_canteen) {
+            (Ok(week), Some(cached)) => sanity::check_canteen(week, &cached.canteen),
+            (Ok(week), None) => sanity::check_canteen(week, &Default::default()),
+            _ => vec![],
+        };
+        results.push(check_week(&format!("Canteen, {}", name), &res, &report, &warnings));
+    }
+
+    for course in config.courses.iter() {
+        let path = cache::cache_path(course)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|e| e);
+        match cache::cache_age(course) {
+            Some(age) => print_check(Health::Ok, "Cache", &format!("{}, {} old", path, format_age(age))),
+            None => print_check(Health::Warn, "Cache", &format!("{}, not written yet", path)),
+        }
+    }
+
+    let quarantined = cache::quarantined();
+    if !quarantined.is_empty() {
+        let dir = quarantined[0].parent().map(|p| p.display().to_string()).unwrap_or_default();
+        print_check(Health::Warn, "Cache", &format!("{} corrupt files moved to {}", quarantined.len(), dir));
+    }
+
+    let failed = results.iter().filter(|h| **h == Health::Fail).count();
+
+    println!();
+    if failed > 0 {
+        return Err(format!("{} of {} checks failed.", failed, results.len()));
+    }
+
+    println!("All {} checks passed.", results.len());
+    Ok(())
+}
+
+fn check_week(
+    name: &str,
+    res: &Result<HashMap<Date<Local>, Vec<String>>, String>,
+    report: &Report,
+    warnings: &[String],
+) -> Health {
+    let health = match res {
+        Err(e) => {
+            print_check(Health::Fail, name, e);
+            Health::Fail
+        }
+        Ok(week) => {
+            let mut days = week.iter().collect::<Vec<_>>();
+            days.sort_by_key(|(d, _)| **d);
+
+            let total: usize = days.iter().map(|(_, items)| count_items(items)).sum();
+
+            // A parser that finds nothing is broken, not just a little off.
+            let health = if days.is_empty() || total == 0 || !report.structure_misses.is_empty() {
+                Health::Fail
+            } else if report.misses.is_empty() && warnings.is_empty() {
+                Health::Ok
+            } else {
+                Health::Warn
+            };
+            print_check(health, name, &format!("{} items on {} days", total, days.len()));
+
+            let per_day = days
+                .iter()
+                .map(|(d, items)| {
+                    format!(
+                        "{} {:02}.{:02}. {}",
+                        &crate::german_weekday(d.weekday())[..2],
+                        d.day(),
+                        d.month(),
+                        count_items(items)
+                    )
+                }).collect::<Vec<String>>();
+            if !per_day.is_empty() {
+                println!("       {}", per_day.join(" | "));
+            }
+
+            for warning in warnings {
+                println!("       anomaly: {}", warning);
+            }
+
+            health
+        }
+    };
+
+    print_report(report);
+    health
+}
+
+fn count_items(items: &[String]) -> usize {
+    items.iter().filter(|i| !i.trim().is_empty()).count()
+}
+
+fn print_check(health: Health, name: &str, detail: &str) {
+    println!("{} {}: {}", health.label(), name, detail);
+}
+
+fn print_report(report: &Report) {
+    for fetch in report.fetches.iter() {
+        println!(
+            "       GET {}  {}  {} ms  {:.1} KiB",
+            fetch.url,
+            fetch.status.map(|s| s.to_string()).unwrap_or_else(|| "---".to_string()),
+            fetch.elapsed.as_millis(),
+            fetch.bytes as f64 / 1024.0
+        );
+    }
+
+    for miss in report.structure_misses.iter() {
+        println!("       selector miss: {} (page structure)", miss);
+    }
+
+    for miss in report.misses.iter() {
+        println!("       selector miss: {}", miss);
+    }
+}
+
+/// Formats a duration like "3 d 4 h", "2 h 5 min" or "42 s".
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+
+    if secs >= 24 * 3600 {
+        format!("{} d {} h", secs / (24 * 3600), secs % (24 * 3600) / 3600)
+    } else if secs >= 3600 {
+        format!("{} h {} min", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{} min", secs / 60)
+    } else {
+        format!("{} s", secs)
+    }
+}
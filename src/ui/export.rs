@@ -0,0 +1,123 @@
+use hs_crawler::canteen_plan;
+use hs_crawler::timetable;
+
+use crate::ui::config::Config;
+use crate::ui::csv;
+use crate::ui::fetch::{self, Fetched, Selection};
+use crate::ui::ics;
+use crate::ui::markdown;
+use crate::ui::merge;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use std::fs;
+use std::io::{self, Write};
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Format {
+    Ics,
+    Csv,
+    Markdown,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ics" => Some(Format::Ics),
+            "csv" => Some(Format::Csv),
+            "markdown" | "md" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("export")
+        .about("Writes the fetched weeks in another format.")
+        .arg(Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["ics", "csv", "markdown", "md"])
+            .default_value("ics")
+            .help("Output format.")
+        ).arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .takes_value(true)
+            .default_value("-")
+            .help("File to write to, - for stdout.")
+        )
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    let format = matches
+        .value_of("format")
+        .and_then(Format::from_name)
+        .ok_or("Unknown format.")?;
+    let selection = Selection::from_matches(matches);
+
+    let mut fetched = fetch::fetch_cached(&selection, &config.courses);
+    config.filter.apply(&mut fetched.timetable);
+
+    for e in fetched.errors.iter() {
+        eprintln!("Warning: {}", e);
+    }
+
+    let out = match format {
+        // A calendar of lunch breaks only if it was asked for explicitly.
+        Format::Ics if !selection.timetable => canteen_ics(&fetched),
+        Format::Ics => timetable_ics(&fetched),
+        Format::Csv => csv::render(&fetched, &selection),
+        Format::Markdown => markdown::render(&fetched, &selection),
+    };
+
+    match matches.value_of("output") {
+        None | Some("-") => {
+            let stdout = io::stdout();
+            let mut lock = stdout.lock();
+            lock.write_all(out.as_bytes())
+                .and_then(|_| lock.flush())
+                .map_err(|e| e.to_string())
+        }
+        Some(path) => fs::write(path, out).map_err(|e| format!("{}: {}", path, e)),
+    }
+}
+
+/// All courses in one calendar, summaries are labelled if there are several.
+pub fn timetable_ics(fetched: &Fetched) -> String {
+    let courses = fetched.timetable.keys().cloned().collect::<Vec<String>>();
+    let several = courses.len() > 1;
+    let mut calendar = ics::Calendar::new(&courses.join(", "));
+
+    for (course, week) in fetched.timetable.iter() {
+        for (date, day) in sorted_days(week) {
+            for mut event in ics::lecture_events(date, &timetable::lectures(course, day)) {
+                event.summary = merge::label(several, course, &event.summary);
+                calendar.push(&event);
+            }
+        }
+    }
+
+    calendar.finish()
+}
+
+pub fn canteen_ics(fetched: &Fetched) -> String {
+    let mut calendar = ics::Calendar::new("Mensa");
+
+    for (date, day) in sorted_days(&fetched.canteen) {
+        for event in ics::canteen_events(date, &canteen_plan::meals(day)) {
+            calendar.push(&event);
+        }
+    }
+
+    calendar.finish()
+}
+
+fn sorted_days<T>(data: &std::collections::HashMap<Date<Local>, T>) -> Vec<(&Date<Local>, &T)> {
+    let mut days = data.iter().collect::<Vec<_>>();
+    days.sort_by_key(|(d, _)| **d);
+    days
+}
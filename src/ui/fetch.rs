@@ -0,0 +1,384 @@
+use hs_crawler;
+
+use crate::ui::cache;
+use crate::ui::changes;
+use crate::ui::config;
+use crate::ui::history;
+use crate::ui::merge::Timetables;
+use crate::AppData;
+
+use clap::{Arg, ArgMatches};
+
+use chrono::prelude::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::mpsc::Receiver;
+
+pub type Week = HashMap<Date<Local>, Vec<String>>;
+
+/// When each week of a source was fetched, by its monday.
+pub type FetchTimes = HashMap<Date<Local>, DateTime<Local>>;
+
+pub fn monday_of(date: &Date<Local>) -> Date<Local> {
+    *date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// Like "cached, 3 days old".
+pub fn cached_label(at: &DateTime<Local>) -> String {
+    let age = Local::now().signed_duration_since(*at);
+
+    let age = if age.num_days() > 1 {
+        format!("{} days", age.num_days())
+    } else if age.num_hours() > 1 {
+        format!("{} hours", age.num_hours())
+    } else {
+        format!("{} min", age.num_minutes().max(0))
+    };
+
+    format!("cached, {} old", age)
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum WeekQuery {
+    This,
+    Next,
+}
+
+impl WeekQuery {
+    pub fn label(self) -> &'static str {
+        match self {
+            WeekQuery::This => "this",
+            WeekQuery::Next => "next",
+        }
+    }
+
+    /// Monday of the week, like the timetable counts weeks.
+    pub fn monday(self) -> Date<Local> {
+        let today = Local::today();
+        let mut monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+
+        if self == WeekQuery::Next {
+            monday = monday + chrono::Duration::days(7);
+        }
+
+        monday
+    }
+
+    pub fn contains(self, date: &Date<Local>) -> bool {
+        let monday = self.monday();
+
+        *date >= monday && *date < monday + chrono::Duration::days(7)
+    }
+
+    fn timetable(self) -> hs_crawler::timetable::Query {
+        match self {
+            WeekQuery::This => hs_crawler::timetable::Query::ThisWeek,
+            WeekQuery::Next => hs_crawler::timetable::Query::NextWeek,
+        }
+    }
+
+    fn canteen(self) -> hs_crawler::canteen_plan::Query {
+        match self {
+            WeekQuery::This => hs_crawler::canteen_plan::Query::ThisWeek,
+            WeekQuery::Next => hs_crawler::canteen_plan::Query::NextWeek,
+        }
+    }
+}
+
+/// Which weeks and sources the non-interactive outputs fetch.
+#[derive(Clone, Debug)]
+pub struct Selection {
+    pub weeks: Vec<WeekQuery>,
+    pub timetable: bool,
+    pub canteen: bool,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection {
+            weeks: vec![WeekQuery::This, WeekQuery::Next],
+            timetable: true,
+            canteen: true,
+        }
+    }
+}
+
+impl Selection {
+    /// `--weeks` and `--sources`, shared by every output.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("weeks")
+                .long("weeks")
+                .takes_value(true)
+                .possible_values(&["this", "next", "all"])
+                .default_value("all")
+                .global(true)
+                .help("Weeks to output."),
+            Arg::with_name("sources")
+                .long("sources")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(&["timetable", "canteen"])
+                .default_value("timetable,canteen")
+                .global(true)
+                .help("Data sources to output."),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let weeks = match matches.value_of("weeks") {
+            Some("this") => vec![WeekQuery::This],
+            Some("next") => vec![WeekQuery::Next],
+            _ => vec![WeekQuery::This, WeekQuery::Next],
+        };
+
+        let sources = matches
+            .values_of("sources")
+            .map(|v| v.collect::<Vec<&str>>())
+            .unwrap_or_else(|| vec!["timetable", "canteen"]);
+
+        Selection {
+            weeks,
+            timetable: sources.contains(&"timetable"),
+            canteen: sources.contains(&"canteen"),
+        }
+    }
+}
+
+/// Course names end up in URLs and file names, so only a safe subset is accepted.
+pub fn is_valid_course(course: &str) -> bool {
+    !course.is_empty() && course.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A fetch that failed, the other sources are still usable.
+#[derive(Clone, Debug)]
+pub struct SourceError {
+    pub source: &'static str,
+    /// Only for timetables.
+    pub course: Option<String>,
+    pub week: WeekQuery,
+    pub message: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.course {
+            Some(ref course) => write!(f, "{} {} ({} week): {}", self.source, course, self.week.label(), self.message),
+            None => write!(f, "{} ({} week): {}", self.source, self.week.label(), self.message),
+        }
+    }
+}
+
+pub struct Fetched {
+    pub timetable: Timetables,
+    pub canteen: Week,
+    pub errors: Vec<SourceError>,
+    pub timetable_fetched: BTreeMap<String, FetchTimes>,
+    pub canteen_fetched: FetchTimes,
+    /// When this was fetched, weeks fetched before came from the cache.
+    pub at: DateTime<Local>,
+}
+
+impl Default for Fetched {
+    fn default() -> Self {
+        Fetched {
+            timetable: Timetables::new(),
+            canteen: Week::new(),
+            errors: vec![],
+            timetable_fetched: BTreeMap::new(),
+            canteen_fetched: FetchTimes::new(),
+            at: Local::now(),
+        }
+    }
+}
+
+impl Fetched {
+    /// When the week of `date` was fetched, if it came from the cache.
+    /// `course` is `None` for the canteen plan.
+    pub fn cached_since(&self, course: Option<&str>, date: &Date<Local>) -> Option<DateTime<Local>> {
+        let times = match course {
+            Some(course) => self.timetable_fetched.get(course)?,
+            None => &self.canteen_fetched,
+        };
+
+        times.get(&monday_of(date)).filter(|at| **at < self.at).cloned()
+    }
+
+    /// What is cached for `course`, like it was just fetched.
+    pub fn from_cache(course: &str, data: AppData) -> Self {
+        let mut fetched = Fetched::default();
+        if !data.timetable.is_empty() {
+            fetched.timetable.insert(course.to_string(), data.timetable);
+        }
+
+        fetched.timetable_fetched.insert(course.to_string(), data.timetable_fetched);
+        fetched.canteen = data.canteen;
+        fetched.canteen_fetched = data.canteen_fetched;
+
+        fetched
+    }
+}
+
+/// Everything cached for `courses`, without touching the network.
+pub fn read_cached(courses: &[String]) -> Fetched {
+    let mut fetched = Fetched::default();
+
+    for course in courses {
+        if let Ok(Some(data)) = cache::read_cache(course) {
+            let cached = Fetched::from_cache(course, data);
+            fetched.timetable.extend(cached.timetable);
+            fetched.timetable_fetched.extend(cached.timetable_fetched);
+            fetched.canteen.extend(cached.canteen);
+            fetched.canteen_fetched.extend(cached.canteen_fetched);
+        }
+    }
+
+    if fetched.canteen.is_empty() {
+        if let Ok(Some(data)) = cache::read_canteen_cache() {
+            fetched.canteen = data.canteen;
+            fetched.canteen_fetched = data.canteen_fetched;
+        }
+    }
+
+    fetched
+}
+
+/// A running crawl: its source, course (none for the canteen), week and result.
+type Pending = (&'static str, Option<String>, WeekQuery, Receiver<Result<Week, String>>);
+
+/// Fetches everything in `selection` for all `courses` in parallel. The
+/// canteen plan is only fetched once. This is blocking, and does nothing offline.
+pub fn fetch(selection: &Selection, courses: &[String]) -> Fetched {
+    let mut fetched = Fetched::default();
+
+    if hs_crawler::offline() {
+        return fetched;
+    }
+
+    let mut pending: Vec<Pending> = vec![];
+
+    for week in selection.weeks.iter() {
+        if selection.timetable {
+            for course in courses {
+                pending.push((
+                    "timetable",
+                    Some(course.clone()),
+                    *week,
+                    hs_crawler::timetable::get_async(week.timetable(), course),
+                ));
+            }
+        }
+
+        if selection.canteen {
+            pending.push((
+                "canteen",
+                None,
+                *week,
+                hs_crawler::canteen_plan::get_async(week.canteen(), &config::canteen()),
+            ));
+        }
+    }
+
+    for (source, course, week, rx) in pending {
+        let res = rx
+            .recv()
+            .unwrap_or_else(|_| Err("Crawler thread died.".to_string()));
+
+        // A week that parsed to nothing isn't fresh, the parser is probably broken.
+        match (res, course) {
+            (Ok(data), Some(course)) => {
+                record(Some(&course), &data, fetched.at);
+                if !data.is_empty() {
+                    fetched.timetable_fetched.entry(course.clone()).or_default().insert(week.monday(), fetched.at);
+                }
+                fetched.timetable.entry(course).or_default().extend(data);
+            }
+            (Ok(data), None) => {
+                record(None, &data, fetched.at);
+                if !data.is_empty() {
+                    fetched.canteen_fetched.insert(week.monday(), fetched.at);
+                }
+                fetched.canteen.extend(data);
+            }
+            (Err(message), course) => fetched.errors.push(SourceError { source, course, week, message }),
+        }
+    }
+
+    fetched
+}
+
+fn record(course: Option<&str>, data: &Week, at: DateTime<Local>) {
+    if let Err(e) = history::record(course, data, at) {
+        eprintln!("Error writing history: {}", e);
+    }
+}
+
+/// Like `fetch`, but keeps cached days of the selected weeks where a fetch
+/// failed, and writes what was fetched back to the cache of each course.
+pub fn fetch_cached(selection: &Selection, courses: &[String]) -> Fetched {
+    let mut fetched = fetch(selection, courses);
+
+    let in_selection = |d: &Date<Local>| selection.weeks.iter().any(|w| w.contains(d));
+
+    for course in courses {
+        let mut data = match cache::read_cache(course) {
+            Ok(Some(data)) => data,
+            _ => cache::read_canteen_cache().ok().flatten().unwrap_or_default(),
+        };
+
+        if selection.timetable {
+            let week = fetched.timetable.entry(course.clone()).or_default();
+
+            let detected = changes::detect(course, week, &data.timetable);
+            if !detected.is_empty() {
+                if let Err(e) = changes::record(detected) {
+                    eprintln!("Error writing changes: {}", e);
+                }
+            }
+
+            for (date, day) in data.timetable.iter() {
+                if in_selection(date) && !week.contains_key(date) {
+                    week.insert(*date, day.clone());
+                }
+            }
+
+            data.timetable.extend(week.clone());
+
+            let times = fetched.timetable_fetched.entry(course.clone()).or_default();
+            merge_times(times, &data.timetable_fetched, &in_selection);
+            data.timetable_fetched.extend(times.clone());
+        }
+
+        for (date, day) in data.canteen.iter() {
+            if selection.canteen && in_selection(date) && !fetched.canteen.contains_key(date) {
+                fetched.canteen.insert(*date, day.clone());
+            }
+        }
+
+        data.canteen.extend(fetched.canteen.clone());
+
+        if selection.canteen {
+            merge_times(&mut fetched.canteen_fetched, &data.canteen_fetched, &in_selection);
+            data.canteen_fetched.extend(fetched.canteen_fetched.clone());
+        }
+
+        if let Err(e) = cache::write_cache(&data, course) {
+            eprintln!("Error writing cache: {}", e);
+        }
+    }
+
+    fetched.timetable.retain(|_, week| !week.is_empty());
+
+    fetched
+}
+
+/// Adds the times of cached weeks in the selection that weren't fetched just now.
+fn merge_times(times: &mut FetchTimes, cached: &FetchTimes, in_selection: &dyn Fn(&Date<Local>) -> bool) {
+    for (monday, at) in cached.iter() {
+        if in_selection(monday) {
+            times.entry(*monday).or_insert(*at);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use hs_crawler::timetable::Lecture;
+
+use crate::ui::merge::Timetables;
+
+/// Personal include/exclude rules for lectures.
+///
+/// A rule is either a text pattern like `Gruppe B`, matched against the whole
+/// entry, or limited to one field like `title:WPF`, `lecturer:Braun`,
+/// `room:E101` or `course:AI3`. Matching ignores case.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filter {
+    /// Lectures matching any of these are hidden,
+    pub exclude: Vec<String>,
+    /// unless they also match one of these.
+    pub include: Vec<String>,
+}
+
+const FIELDS: [&str; 5] = ["title", "lecturer", "room", "course", "text"];
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.exclude.is_empty()
+    }
+
+    pub fn hides(&self, lecture: &Lecture) -> bool {
+        self.exclude.iter().any(|r| matches(r, lecture)) && !self.include.iter().any(|r| matches(r, lecture))
+    }
+
+    /// Like `hides`, for a cell as crawled.
+    pub fn hides_cell(&self, course: &str, slot: usize, text: &str) -> bool {
+        match Lecture::parse(course, slot, text) {
+            Some(lecture) => self.hides(&lecture),
+            None => false,
+        }
+    }
+
+    /// Empties the cells of hidden lectures, so slots stay where they are.
+    pub fn apply(&self, timetables: &mut Timetables) {
+        if self.is_empty() {
+            return;
+        }
+
+        for (course, week) in timetables.iter_mut() {
+            for day in week.values_mut() {
+                for (slot, cell) in day.iter_mut().enumerate() {
+                    if self.hides_cell(course, slot, cell) {
+                        cell.clear();
+                    }
+                }
+            }
+        }
+    }
+
+    /// The rule that hides `lecture` and everything with the same title.
+    pub fn rule_for(lecture: &Lecture) -> String {
+        format!("title:{}", lecture.title)
+    }
+}
+
+fn matches(rule: &str, lecture: &Lecture) -> bool {
+    let (field, pattern) = match rule.find(':') {
+        Some(i) if FIELDS.contains(&&rule[..i]) => (&rule[..i], &rule[i + 1..]),
+        _ => ("text", rule),
+    };
+
+    let pattern = pattern.trim().to_lowercase();
+    if pattern.is_empty() {
+        return false;
+    }
+
+    let value = match field {
+        "title" => Some(&lecture.title),
+        "lecturer" => lecture.lecturer.as_ref(),
+        "room" => lecture.room.as_ref(),
+        "course" => Some(&lecture.course),
+        _ => Some(&lecture.text),
+    };
+
+    value.map(|v| v.to_lowercase().contains(&pattern)).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::prelude::*;
+
+    fn lecture(course: &str, text: &str) -> Lecture {
+        Lecture::parse(course, 1, text).unwrap()
+    }
+
+    fn filter(exclude: &[&str], include: &[&str]) -> Filter {
+        Filter {
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            include: include.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn plain_rules_match_the_whole_entry_ignoring_case() {
+        let wpf = lecture("AI3", "WPF Robotik\nBraun\nE101\nGruppe B");
+
+        assert!(filter(&["gruppe b"], &[]).hides(&wpf));
+        assert!(filter(&["BRAUN"], &[]).hides(&wpf));
+        assert!(!filter(&["Gruppe A"], &[]).hides(&wpf));
+    }
+
+    #[test]
+    fn field_rules_only_match_their_field() {
+        let wpf = lecture("AI3", "WPF Robotik\nBraun\nE101");
+
+        assert!(filter(&["title:wpf"], &[]).hides(&wpf));
+        assert!(filter(&["lecturer:braun"], &[]).hides(&wpf));
+        assert!(filter(&["room:e101"], &[]).hides(&wpf));
+        assert!(filter(&["course:ai3"], &[]).hides(&wpf));
+
+        assert!(!filter(&["title:Braun"], &[]).hides(&wpf));
+        assert!(!filter(&["room:Robotik"], &[]).hides(&wpf));
+        assert!(!filter(&["course:AI4"], &[]).hides(&wpf));
+    }
+
+    #[test]
+    fn unknown_prefixes_and_empty_patterns() {
+        let talk = lecture("AI3", "Vortrag: Rust\nE101");
+
+        // Not a field, so the whole rule is a text pattern.
+        assert!(filter(&["Vortrag: Rust"], &[]).hides(&talk));
+        assert!(!filter(&["title:", "  "], &[]).hides(&talk));
+    }
+
+    #[test]
+    fn include_wins_over_exclude() {
+        let robotik = lecture("AI3", "WPF Robotik\nBraun");
+        let grafik = lecture("AI3", "WPF Grafik\nKlein");
+        let filter = filter(&["title:WPF"], &["Robotik"]);
+
+        assert!(!filter.hides(&robotik));
+        assert!(filter.hides(&grafik));
+        assert!(!filter.hides(&lecture("AI3", "Mathematik 2")));
+    }
+
+    #[test]
+    fn apply_empties_hidden_cells_in_place() {
+        let day = Local.ymd(2024, 4, 8);
+        let cells = vec!["WPF Grafik\nKlein".to_string(), String::new(), "Mathematik 2\nE101".to_string()];
+
+        let mut timetables = Timetables::new();
+        timetables.insert("AI3".to_string(), vec![(day, cells)].into_iter().collect());
+
+        filter(&["grafik"], &[]).apply(&mut timetables);
+        assert_eq!(timetables["AI3"][&day], vec!["", "", "Mathematik 2\nE101"]);
+
+        filter(&[], &["Mathematik"]).apply(&mut timetables);
+        assert_eq!(timetables["AI3"][&day][2], "Mathematik 2\nE101");
+    }
+}
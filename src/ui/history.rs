@@ -0,0 +1,265 @@
+use hs_crawler::canteen_plan;
+
+use crate::ui::config::{self, Config};
+use crate::ui::fetch::{monday_of, Fetched, Week};
+use crate::ui::plain::{self, parse_date};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nix::fcntl::{flock, FlockArg};
+
+/// Set from the config, the archive is opt-in.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// One fetched week, a line of the archive.
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    fetched_at: DateTime<Local>,
+    monday: NaiveDate,
+    days: BTreeMap<NaiveDate, Vec<String>>,
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("history")
+        .about("Queries the archive of every fetched week, see \"history\" in the config.")
+        .subcommand(SubCommand::with_name("day")
+            .about("Prints the timetable and canteen plan of a past day as last fetched.")
+            .arg(Arg::with_name("date")
+                .required(true)
+                .help("YYYY-MM-DD, DD.MM.YYYY or DD.MM.")
+            )
+        ).subcommand(SubCommand::with_name("meal")
+            .about("Counts how often a meal was served, this semester by default.")
+            .arg(Arg::with_name("text")
+                .required(true)
+                .help("Part of the meal, like Schnitzel.")
+            ).arg(Arg::with_name("from")
+                .long("from")
+                .takes_value(true)
+                .help("First day, inclusive.")
+            ).arg(Arg::with_name("to")
+                .long("to")
+                .takes_value(true)
+                .help("Last day, inclusive.")
+            )
+        )
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// `{data dir}/hs_app/history`, apart from the cache that is cleaned up.
+pub fn history_dir() -> Result<PathBuf, String> {
+    let mut path = dirs::data_dir().ok_or("Unable to find data dir.")?;
+    path.push("hs_app");
+    path.push("history");
+
+    Ok(path)
+}
+
+/// One file per timetable and canteen, `course` is `None` for the canteen plan.
+fn history_path(course: Option<&str>) -> Result<PathBuf, String> {
+    let name = match course {
+        Some(course) => format!("timetable-{}", course),
+        None => format!("canteen-{}", config::canteen()),
+    };
+
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect::<String>();
+
+    Ok(history_dir()?.join(format!("{}.jsonl", name)))
+}
+
+/// Appends the weeks of `days` fetched at `at`, unless they didn't change
+/// since they were archived last. Does nothing if the archive is disabled.
+pub fn record(course: Option<&str>, days: &Week, at: DateTime<Local>) -> Result<(), String> {
+    if !ENABLED.load(Ordering::Relaxed) || days.is_empty() {
+        return Ok(());
+    }
+
+    let path = history_path(course)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    flock(file.as_raw_fd(), FlockArg::LockExclusive).map_err(|e| format!("Unable to lock history: {}", e))?;
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let archived = parse(&contents);
+
+    let mut weeks: BTreeMap<NaiveDate, BTreeMap<NaiveDate, Vec<String>>> = BTreeMap::new();
+    for (date, day) in days.iter() {
+        weeks.entry(monday_of(date).naive_local()).or_default().insert(date.naive_local(), day.clone());
+    }
+
+    let mut out = String::new();
+    for (monday, days) in weeks {
+        let unchanged = archived
+            .iter()
+            .rev()
+            .find(|e| e.monday == monday)
+            .map(|e| e.days == days)
+            .unwrap_or(false);
+
+        if unchanged {
+            continue;
+        }
+
+        let entry = Entry { fetched_at: at, monday, days };
+        out += &serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        out.push('\n');
+    }
+
+    file.write_all(out.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Lines that can't be read are skipped, one broken line shouldn't cost the archive.
+fn parse(contents: &str) -> Vec<Entry> {
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn read(course: Option<&str>) -> Result<Vec<Entry>, String> {
+    let path = history_path(course)?;
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(parse(&contents)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// The last fetched version of every day, with when it was fetched.
+fn latest(entries: Vec<Entry>) -> BTreeMap<Date<Local>, (DateTime<Local>, Vec<String>)> {
+    let mut latest: BTreeMap<Date<Local>, (DateTime<Local>, Vec<String>)> = BTreeMap::new();
+
+    for entry in entries {
+        for (date, day) in entry.days {
+            let date = match Local.from_local_date(&date).single() {
+                Some(date) => date,
+                None => continue,
+            };
+
+            let newer = latest.get(&date).map(|(at, _)| entry.fetched_at >= *at).unwrap_or(true);
+            if newer {
+                latest.insert(date, (entry.fetched_at, day));
+            }
+        }
+    }
+
+    latest
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    match matches.subcommand() {
+        ("day", Some(sub)) => day(config, parse_date(sub.value_of("date").unwrap_or(""))?),
+        ("meal", Some(sub)) => meal(sub),
+        _ => Err("Use `history day DATE` or `history meal TEXT`, see --help.".to_string()),
+    }
+}
+
+fn day(config: &Config, date: Date<Local>) -> Result<(), String> {
+    let mut data = Fetched::default();
+
+    for course in config.courses.iter() {
+        if let Some((at, day)) = latest(read(Some(course))?).remove(&date) {
+            data.timetable.entry(course.clone()).or_default().insert(date, day);
+            data.timetable_fetched.entry(course.clone()).or_default().insert(monday_of(&date), at);
+        }
+    }
+
+    if let Some((at, day)) = latest(read(None)?).remove(&date) {
+        data.canteen.insert(date, day);
+        data.canteen_fetched.insert(monday_of(&date), at);
+    }
+
+    if data.timetable.is_empty() && data.canteen.is_empty() {
+        return Err(not_archived(config, &format!("{:02}.{:02}.{}", date.day(), date.month(), date.year())));
+    }
+
+    plain::print_days(config, &[date], data, false)
+}
+
+fn meal(matches: &ArgMatches) -> Result<(), String> {
+    let text = matches.value_of("text").unwrap_or("").trim().to_lowercase();
+    let (first, last) = semester(Local::today());
+
+    let from = match matches.value_of("from") {
+        Some(from) => parse_date(from)?,
+        None => first,
+    };
+    let to = match matches.value_of("to") {
+        Some(to) => parse_date(to)?,
+        None => last,
+    };
+
+    if from > to {
+        return Err("--from is after --to.".to_string());
+    }
+
+    let mut count = 0;
+    for (date, (_, day)) in latest(read(None)?).range(from..=to) {
+        for m in canteen_plan::meals(day) {
+            if m.description.to_lowercase().contains(&text) {
+                println!("{:02}.{:02}.{}  {}", date.day(), date.month(), date.year(), m.description);
+                count += 1;
+            }
+        }
+    }
+
+    let times = match count {
+        1 => "once".to_string(),
+        n => format!("{} times", n),
+    };
+
+    println!(
+        "{} served {} between {:02}.{:02}.{} and {:02}.{:02}.{}.",
+        matches.value_of("text").unwrap_or(""),
+        times,
+        from.day(), from.month(), from.year(),
+        to.day(), to.month(), to.year()
+    );
+
+    Ok(())
+}
+
+/// First and last day of the semester `day` is in, summer from March to August.
+fn semester(day: Date<Local>) -> (Date<Local>, Date<Local>) {
+    let date = |y, m, d| Local.ymd(y, m, d);
+
+    match day.month() {
+        3..=8 => (date(day.year(), 3, 1), date(day.year(), 8, 31)),
+        9..=12 => (date(day.year(), 9, 1), date(day.year() + 1, 3, 1).pred()),
+        _ => (date(day.year() - 1, 9, 1), date(day.year(), 3, 1).pred()),
+    }
+}
+
+fn not_archived(config: &Config, what: &str) -> String {
+    if config.history {
+        format!("Nothing archived for {}.", what)
+    } else {
+        format!("Nothing archived for {}, enable \"history\" in the config to keep every fetched week.", what)
+    }
+}
@@ -0,0 +1,214 @@
+//! Just enough HTTP/1.1 for `serve` and `api`, one thread per connection.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const READ_TIMEOUT_SEC: u64 = 10;
+const MAX_HEADER_LINES: usize = 100;
+
+pub struct Request {
+    pub method: String,
+    /// Percent decoded, without query.
+    pub path: String,
+    pub query: HashMap<String, String>,
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: String) -> Self {
+        Response {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn text(status: u16, body: &str) -> Self {
+        Response::new(status, "text/plain; charset=utf-8", format!("{}\n", body))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+/// Listens on `bind` and answers every request with `handler`. Never returns on success.
+pub fn serve<F>(bind: &str, handler: F) -> Result<(), String>
+where
+    F: 'static + Send + Sync + Fn(&Request) -> Response,
+{
+    let listener = TcpListener::bind(bind).map_err(|e| format!("Unable to listen on {}: {}", bind, e))?;
+    let handler = Arc::new(handler);
+
+    for stream in listener.incoming().flatten() {
+        let handler = handler.clone();
+
+        thread::spawn(move || {
+            let _ = handle(stream, &*handler);
+        });
+    }
+
+    Ok(())
+}
+
+fn handle<F>(mut stream: TcpStream, handler: &F) -> std::io::Result<()>
+where
+    F: Fn(&Request) -> Response,
+{
+    stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SEC)))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let response = match parse_request_line(&request_line) {
+        Some(ref req) if req.method == "GET" || req.method == "HEAD" => handler(req),
+        Some(_) => Response::text(405, "Only GET is supported."),
+        None => Response::text(400, "Malformed request."),
+    };
+
+    let head_only = request_line.starts_with("HEAD ");
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )?;
+    if !head_only {
+        stream.write_all(response.body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+fn parse_request_line(line: &str) -> Option<Request> {
+    let mut parts = line.split_whitespace();
+
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, ""),
+    };
+
+    let query = query
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let mut split = kv.splitn(2, '=');
+            let k = split.next().unwrap_or("");
+            let v = split.next().unwrap_or("");
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect();
+
+    Some(Request {
+        method,
+        path: percent_decode(path),
+        query,
+    })
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                // `from_str_radix` alone would take "+1" as well.
+                let hex = Some(&bytes[i + 1..i + 3])
+                    .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|h| std::str::from_utf8(h).ok());
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escapes_and_plus() {
+        assert_eq!(percent_decode("AI3"), "AI3");
+        assert_eq!(percent_decode("a%20b+c"), "a b c");
+        assert_eq!(percent_decode("M%C3%BCller"), "Müller");
+        assert_eq!(percent_decode("%2f%2F"), "//");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        assert_eq!(percent_decode("%zz"), "%zz");
+        assert_eq!(percent_decode("%+1"), "% 1");
+        assert_eq!(percent_decode("%-1"), "%-1");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%4"), "%4");
+        assert_eq!(percent_decode("%%41"), "%A");
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        assert_eq!(percent_decode("%FF"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn parses_path_and_query() {
+        let req = parse_request_line("GET /calendar/ai%203.ics?from=2026-10-19&x&to= HTTP/1.1\r\n").unwrap();
+
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/calendar/ai 3.ics");
+        assert_eq!(req.query.get("from").map(String::as_str), Some("2026-10-19"));
+        assert_eq!(req.query.get("x").map(String::as_str), Some(""));
+        assert_eq!(req.query.get("to").map(String::as_str), Some(""));
+    }
+
+    #[test]
+    fn rejects_lines_without_target() {
+        assert!(parse_request_line("").is_none());
+        assert!(parse_request_line("GET").is_none());
+    }
+}
@@ -0,0 +1,273 @@
+use hs_crawler::canteen_plan::Meal;
+use hs_crawler::timetable::Lecture;
+
+use crate::ui::config;
+
+use chrono::prelude::*;
+
+pub const TZID: &str = "Europe/Berlin";
+
+const VTIMEZONE: &str = "\
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+TZNAME:CEST\r
+DTSTART:19700329T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+TZNAME:CET\r
+DTSTART:19701025T030000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+";
+
+/// A VEVENT, times are local to `TZID`.
+pub struct Event {
+    /// Must not change between exports, otherwise clients duplicate events.
+    pub uid: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub summary: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Builds a VCALENDAR, see RFC 5545.
+pub struct Calendar {
+    out: String,
+    stamp: String,
+}
+
+impl Calendar {
+    pub fn new(name: &str) -> Self {
+        let mut cal = Calendar {
+            out: String::new(),
+            stamp: Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+        };
+
+        cal.line("BEGIN:VCALENDAR");
+        cal.line("VERSION:2.0");
+        cal.line(&format!("PRODID:-//hs_app//HS APP {}//DE", crate::VERSION));
+        cal.line("CALSCALE:GREGORIAN");
+        cal.line("METHOD:PUBLISH");
+        cal.line(&format!("X-WR-CALNAME:{}", escape(name)));
+        cal.line(&format!("X-WR-TIMEZONE:{}", TZID));
+        cal.out.push_str(VTIMEZONE);
+
+        cal
+    }
+
+    pub fn push(&mut self, event: &Event) {
+        let stamp = self.stamp.clone();
+
+        self.line("BEGIN:VEVENT");
+        self.line(&format!("UID:{}", event.uid));
+        self.line(&format!("DTSTAMP:{}", stamp));
+        self.line(&format!("DTSTART;TZID={}:{}", TZID, event.start.format("%Y%m%dT%H%M%S")));
+        self.line(&format!("DTEND;TZID={}:{}", TZID, event.end.format("%Y%m%dT%H%M%S")));
+        self.line(&format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(ref location) = event.location {
+            self.line(&format!("LOCATION:{}", escape(location)));
+        }
+        if let Some(ref description) = event.description {
+            self.line(&format!("DESCRIPTION:{}", escape(description)));
+        }
+        self.line("END:VEVENT");
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        self.out
+    }
+
+    /// Writes a content line, folded to 75 octets.
+    fn line(&mut self, content: &str) {
+        let mut width = 0;
+
+        for ch in content.chars() {
+            if width + ch.len_utf8() > 75 {
+                self.out.push_str("\r\n ");
+                width = 1;
+            }
+
+            self.out.push(ch);
+            width += ch.len_utf8();
+        }
+
+        self.out.push_str("\r\n");
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => (),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// "mensa-offenburg" as in the SWFR url becomes "Mensa Offenburg".
+fn canteen_name(slug: &str) -> String {
+    slug.split('-')
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// One event per lecture, back to back slots of the same lecture are merged.
+pub fn lecture_events(date: &Date<Local>, lectures: &[Lecture]) -> Vec<Event> {
+    // (first slot of the lecture, last slot, end time)
+    let mut merged: Vec<(&Lecture, usize, NaiveTime)> = vec![];
+
+    for lecture in lectures {
+        let end = match lecture.end {
+            Some(e) if lecture.start.is_some() => e,
+            _ => continue,
+        };
+
+        if let Some((first, last_slot, last_end)) = merged.last_mut() {
+            if first.text == lecture.text && first.course == lecture.course && *last_slot + 1 == lecture.slot {
+                *last_slot = lecture.slot;
+                *last_end = end;
+                continue;
+            }
+        }
+
+        merged.push((lecture, lecture.slot, end));
+    }
+
+    let day = date.naive_local();
+
+    merged
+        .into_iter()
+        .map(|(lecture, _, end)| {
+            let mut description = vec![];
+            if let Some(ref lecturer) = lecture.lecturer {
+                description.push(lecturer.clone());
+            }
+            description.push(lecture.course.clone());
+
+            Event {
+                uid: format!(
+                    "{}-{}-{}@hs_app",
+                    day.format("%Y%m%d"),
+                    lecture.slot,
+                    lecture.course.to_lowercase()
+                ),
+                start: day.and_time(lecture.start.unwrap()),
+                end: day.and_time(end),
+                summary: lecture.title.clone(),
+                location: lecture.room.clone(),
+                description: Some(description.join("\n")),
+            }
+        })
+        .collect()
+}
+
+/// Lunch break of the canteen, used for the canteen calendar.
+pub const CANTEEN_OPEN: (u32, u32) = (11, 30);
+pub const CANTEEN_CLOSE: (u32, u32) = (14, 0);
+
+/// One event per day, listing all meals.
+pub fn canteen_events(date: &Date<Local>, meals: &[Meal]) -> Vec<Event> {
+    if meals.is_empty() {
+        return vec![];
+    }
+
+    let day = date.naive_local();
+
+    let description = meals
+        .iter()
+        .map(|m| match m.name {
+            Some(ref name) => format!("{}: {}", name, m.description),
+            None => m.description.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    vec![Event {
+        uid: format!("{}-canteen@hs_app", day.format("%Y%m%d")),
+        start: day.and_hms(CANTEEN_OPEN.0, CANTEEN_OPEN.1, 0),
+        end: day.and_hms(CANTEEN_CLOSE.0, CANTEEN_CLOSE.1, 0),
+        summary: format!("Mensa: {}", meals[0].description),
+        location: Some(canteen_name(&config::canteen())),
+        description: Some(description),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar_lines(content: &str) -> String {
+        let mut cal = Calendar { out: String::new(), stamp: String::new() };
+        cal.line(content);
+        cal.out
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape("a,b;c\\d"), "a\\,b\\;c\\\\d");
+        assert_eq!(escape("line\r\nnext"), "line\\nnext");
+        assert_eq!(escape("Gemüse"), "Gemüse");
+    }
+
+    #[test]
+    fn keeps_short_lines() {
+        let line = "x".repeat(75);
+        assert_eq!(calendar_lines(&line), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn folds_long_lines_at_75_octets() {
+        let out = calendar_lines(&"x".repeat(160));
+        let lines = out.split("\r\n").collect::<Vec<&str>>();
+
+        assert_eq!(lines, vec![
+            "x".repeat(75).as_str(),
+            &format!(" {}", "x".repeat(74)),
+            &format!(" {}", "x".repeat(11)),
+            "",
+        ]);
+    }
+
+    #[test]
+    fn never_splits_a_character() {
+        // "ü" is two octets, the 38th would end at octet 76.
+        let out = calendar_lines(&format!("x{}", "ü".repeat(40)));
+
+        for line in out.split("\r\n") {
+            assert!(line.len() <= 75, "{} octets: {}", line.len(), line);
+        }
+
+        assert_eq!(out.replace("\r\n ", "").trim_end(), format!("x{}", "ü".repeat(40)));
+    }
+
+    #[test]
+    fn names_the_canteen() {
+        assert_eq!(canteen_name("mensa-offenburg"), "Mensa Offenburg");
+        assert_eq!(canteen_name("mensa-rempartstrasse"), "Mensa Rempartstrasse");
+    }
+}
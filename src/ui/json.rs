@@ -1,36 +1,156 @@
-// TODO: Remove?
-use hs_crawler;
+use hs_crawler::canteen_plan::{self, Meal};
+use hs_crawler::timetable::Lecture;
 use serde_json;
 
-use std::collections::HashMap;
+use crate::ui::config::Config;
+use crate::ui::fetch::{self, Selection, SourceError};
+use crate::ui::merge::{self, Timetables};
 
-#[derive(Serialize, Deserialize, Debug)]
+use chrono::prelude::*;
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Bump this whenever the output changes in a way scripts could notice.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
 struct JsonState {
-    timetable: HashMap<String, String>,
-    canteen: HashMap<String, String>,
+    schema_version: u32,
+    generated_at: String,
+    /// The first of `courses`.
+    course: String,
+    courses: Vec<String>,
+    /// Keys are ISO dates (`YYYY-MM-DD`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timetable: Option<BTreeMap<String, Vec<JsonLecture>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canteen: Option<BTreeMap<String, Vec<JsonMeal>>>,
+    errors: Vec<JsonError>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JsonLecture {
+    course: String,
+    slot: usize,
+    start: Option<String>,
+    end: Option<String>,
+    title: String,
+    lecturer: Option<String>,
+    room: Option<String>,
+    text: String,
+}
+
+impl From<Lecture> for JsonLecture {
+    fn from(l: Lecture) -> Self {
+        JsonLecture {
+            course: l.course,
+            slot: l.slot,
+            start: l.start.map(|t| t.format("%H:%M").to_string()),
+            end: l.end.map(|t| t.format("%H:%M").to_string()),
+            title: l.title,
+            lecturer: l.lecturer,
+            room: l.room,
+            text: l.text,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct JsonMeal {
+    name: Option<String>,
+    description: String,
+    allergens: Vec<String>,
+    labels: Vec<String>,
+    text: String,
+}
+
+impl From<Meal> for JsonMeal {
+    fn from(m: Meal) -> Self {
+        JsonMeal {
+            name: m.name,
+            description: m.description,
+            allergens: m.allergens,
+            labels: m.labels,
+            text: m.text,
+        }
+    }
 }
 
-pub fn print_as_json(course : &str) {
+#[derive(Serialize, Debug)]
+pub struct JsonError {
+    source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    course: Option<String>,
+    week: String,
+    message: String,
+}
+
+impl From<SourceError> for JsonError {
+    fn from(e: SourceError) -> Self {
+        JsonError {
+            source: e.source.to_string(),
+            course: e.course,
+            week: e.week.label().to_string(),
+            message: e.message,
+        }
+    }
+}
+
+pub fn iso_date(date: &Date<Local>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// All courses merged per day, see `merge::lectures`.
+pub fn lectures_by_date(timetables: &Timetables) -> BTreeMap<String, Vec<JsonLecture>> {
+    merge::days(timetables)
+        .iter()
+        .map(|d| (
+            iso_date(d),
+            merge::lectures(timetables, d)
+                .unwrap_or_default()
+                .into_iter()
+                .map(JsonLecture::from)
+                .collect()))
+        .collect()
+}
+
+pub fn print_as_json(config : &Config, selection : &Selection) -> Result<(), String> {
+    let courses = &config.courses;
+
+    // Weeks that can't be fetched come from the cache, errors still list them.
+    let mut fetched = fetch::fetch_cached(selection, courses);
+    config.filter.apply(&mut fetched.timetable);
 
     let state = JsonState {
-        timetable: hs_crawler::timetable::get(hs_crawler::timetable::Query::ThisWeek, course)
-            .unwrap_or(Default::default())
-            .into_iter()
-            .map(|(k, v)| (
-                k.to_string(),
-                v.into_iter().fold(String::new(), |a,b|a+&b)))
-            .collect(),
-        canteen: hs_crawler::canteen_plan::get(hs_crawler::canteen_plan::Query::ThisWeek)
-            .unwrap_or(Default::default())
-            .into_iter()
-            .map(|(k, v)| (
-                k.to_string(),
-                v.into_iter().fold(String::new(), |a,b|a+&b)))
-            .collect(),
+        schema_version: SCHEMA_VERSION,
+        generated_at: Local::now().to_rfc3339(),
+        course: courses[0].clone(),
+        courses: courses.to_vec(),
+        timetable: if selection.timetable {
+            Some(lectures_by_date(&fetched.timetable))
+        } else {
+            None
+        },
+        canteen: if selection.canteen {
+            Some(fetched.canteen
+                .iter()
+                .map(|(k, v)| (
+                    iso_date(k),
+                    canteen_plan::meals(v).into_iter().map(JsonMeal::from).collect()))
+                .collect())
+        } else {
+            None
+        },
+        errors: fetched.errors.into_iter().map(JsonError::from).collect(),
     };
 
-    let out = serde_json::to_string_pretty(&state).expect("Could not print JSON, somehow.");
+    let out = serde_json::to_string_pretty(&state).map_err(|e| e.to_string())?;
 
-    println!("{}", out);
-
-}
\ No newline at end of file
+    let mut stdout = io::stdout().lock();
+    match writeln!(stdout, "{}", out).and_then(|_| stdout.flush()) {
+        // Like `hs_app --json | head -1`, whoever reads is done.
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(|e| e.to_string()),
+    }
+}
@@ -0,0 +1,162 @@
+use hs_crawler::canteen_plan;
+use hs_crawler::timetable::{self, Lecture};
+
+use crate::ui::fetch::{monday_of, Fetched, Selection};
+use crate::ui::merge::{self, Timetables};
+
+use chrono::prelude::*;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+type Week<'a> = BTreeMap<Date<Local>, &'a Vec<String>>;
+
+/// A week grid per source and week, like the table view of the TUI.
+pub fn render(fetched: &Fetched, selection: &Selection) -> String {
+    let mut out = String::new();
+
+    if selection.timetable {
+        let courses = fetched.timetable.keys().cloned().collect::<Vec<String>>().join(", ");
+        let mut weeks: BTreeMap<Date<Local>, Vec<Date<Local>>> = BTreeMap::new();
+
+        for date in merge::days(&fetched.timetable) {
+            weeks.entry(monday_of(&date)).or_default().push(date);
+        }
+
+        for (monday, days) in weeks {
+            writeln!(out, "## Stundenplan {}, Woche vom {}\n", courses, long_date(&monday)).unwrap();
+            timetable_grid(&mut out, &fetched.timetable, &days);
+            out.push('\n');
+        }
+    }
+
+    if selection.canteen {
+        for (monday, week) in by_week(&fetched.canteen) {
+            writeln!(out, "## Mensa, Woche vom {}\n", long_date(&monday)).unwrap();
+            canteen_grid(&mut out, &week);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Days with overlapping lectures get a column per course, side by side.
+/// Only cells of columns shared by several courses are labelled with theirs.
+fn timetable_grid(out: &mut String, timetables: &Timetables, days: &[Date<Local>]) {
+    let several = timetables.len() > 1;
+    let mut columns: Vec<(String, bool, Vec<Lecture>)> = vec![];
+
+    for date in days {
+        let lectures = merge::lectures(timetables, date).unwrap_or_default();
+        let overlaps = lectures.windows(2).any(|w| w[0].slot == w[1].slot);
+
+        if !overlaps {
+            columns.push((day_header(date), several, lectures));
+            continue;
+        }
+
+        let courses = lectures.iter().map(|l| l.course.clone()).collect::<BTreeSet<String>>();
+        for course in courses {
+            let own = lectures.iter().filter(|l| l.course == course).cloned().collect();
+            columns.push((format!("{} ({})", day_header(date), course), false, own));
+        }
+    }
+
+    let slots = columns
+        .iter()
+        .flat_map(|(_, _, lectures)| lectures.iter().map(|l| l.slot + 1))
+        .max()
+        .unwrap_or(0);
+
+    header(out, "Zeit", &columns.iter().map(|(h, _, _)| h.clone()).collect::<Vec<String>>());
+
+    for slot in 0..slots {
+        let cells = columns
+            .iter()
+            .map(|(_, labelled, lectures)| {
+                lectures
+                    .iter()
+                    .find(|l| l.slot == slot)
+                    .map(|l| {
+                        let title = format!("**{}**", escape(&l.title));
+                        let mut parts = vec![merge::label(*labelled, &l.course, &title)];
+                        parts.extend(l.room.iter().map(|r| escape(r)));
+                        parts.extend(l.lecturer.iter().map(|r| escape(r)));
+                        parts.join("<br>")
+                    })
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<String>>();
+
+        if cells.iter().all(|c| c.is_empty()) {
+            continue;
+        }
+
+        let time = match timetable::slot_time(slot) {
+            Some((start, end)) => format!("{}–{}", start.format("%H:%M"), end.format("%H:%M")),
+            None => format!("{}.", slot + 1),
+        };
+
+        row(out, &time, &cells);
+    }
+}
+
+fn canteen_grid(out: &mut String, week: &Week) {
+    let days = week
+        .values()
+        .map(|day| canteen_plan::meals(day))
+        .collect::<Vec<_>>();
+
+    let rows = days.iter().map(|d| d.len()).max().unwrap_or(0);
+
+    header(out, "", &week.keys().map(day_header).collect::<Vec<String>>());
+
+    for i in 0..rows {
+        let cells = days
+            .iter()
+            .map(|meals| {
+                meals
+                    .get(i)
+                    .map(|m| match m.name {
+                        Some(ref name) => format!("**{}**<br>{}", escape(name), escape(&m.description)),
+                        None => escape(&m.description),
+                    })
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<String>>();
+
+        row(out, &format!("{}.", i + 1), &cells);
+    }
+}
+
+fn day_header(d: &Date<Local>) -> String {
+    format!("{} {:02}.{:02}.", &crate::german_weekday(d.weekday())[..2], d.day(), d.month())
+}
+
+fn header(out: &mut String, first: &str, days: &[String]) {
+    writeln!(out, "| {} | {} |", first, days.join(" | ")).unwrap();
+    writeln!(out, "|---|{}", "---|".repeat(days.len())).unwrap();
+}
+
+fn row(out: &mut String, first: &str, cells: &[String]) {
+    writeln!(out, "| {} | {} |", first, cells.join(" | ")).unwrap();
+}
+
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+fn long_date(date: &Date<Local>) -> String {
+    format!("{:02}.{:02}.{}", date.day(), date.month(), date.year())
+}
+
+fn by_week(data: &HashMap<Date<Local>, Vec<String>>) -> BTreeMap<Date<Local>, Week<'_>> {
+    let mut weeks: BTreeMap<Date<Local>, Week> = BTreeMap::new();
+
+    for (date, day) in data.iter() {
+        weeks.entry(monday_of(date)).or_default().insert(*date, day);
+    }
+
+    weeks
+}
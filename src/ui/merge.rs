@@ -0,0 +1,69 @@
+use hs_crawler::timetable::{self, Lecture};
+
+use crate::ui::fetch::Week;
+
+use chrono::prelude::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The timetables of several courses, by course.
+pub type Timetables = BTreeMap<String, Week>;
+
+/// Every day any of the courses has.
+pub fn days(timetables: &Timetables) -> BTreeSet<Date<Local>> {
+    timetables.values().flat_map(|week| week.keys().cloned()).collect()
+}
+
+/// The lectures of all courses on `date`, by slot and then course.
+/// `None` if no course has that day at all.
+pub fn lectures(timetables: &Timetables, date: &Date<Local>) -> Option<Vec<Lecture>> {
+    let mut found = false;
+    let mut lectures = vec![];
+
+    for (course, week) in timetables.iter() {
+        if let Some(day) = week.get(date) {
+            found = true;
+            lectures.extend(timetable::lectures(course, day));
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    lectures.sort_by(|a, b| (a.slot, &a.course).cmp(&(b.slot, &b.course)));
+    Some(lectures)
+}
+
+/// The raw cells of all courses on `date` by slot, each with its course.
+/// Empty cells are left out, so a slot with more than one entry overlaps.
+pub fn cells<'a>(timetables: &'a Timetables, date: &Date<Local>) -> Option<Vec<Vec<(&'a str, &'a str)>>> {
+    let days = timetables
+        .iter()
+        .filter_map(|(course, week)| week.get(date).map(|day| (course, day)))
+        .collect::<Vec<_>>();
+
+    if days.is_empty() {
+        return None;
+    }
+
+    let slots = days.iter().map(|(_, day)| day.len()).max().unwrap_or(0);
+
+    Some((0..slots)
+        .map(|slot| {
+            days.iter()
+                .filter_map(|(course, day)| day.get(slot).map(|text| (course.as_str(), text.as_str())))
+                .filter(|(_, text)| !text.trim().is_empty())
+                .collect()
+        })
+        .collect())
+}
+
+/// Prefixes `text` with its course when several courses are shown.
+pub fn label(several: bool, course: &str, text: &str) -> String {
+    if several {
+        format!("[{}] {}", course, text)
+    } else {
+        text.to_string()
+    }
+}
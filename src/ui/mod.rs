@@ -1,3 +1,23 @@
 pub mod json;
 pub mod theme;
-pub mod cache;
\ No newline at end of file
+pub mod api;
+pub mod cache;
+pub mod changes;
+pub mod config;
+pub mod csv;
+pub mod diet;
+pub mod doctor;
+pub mod export;
+pub mod fetch;
+pub mod filter;
+pub mod history;
+pub mod http;
+pub mod ics;
+pub mod markdown;
+pub mod merge;
+pub mod plain;
+pub mod remind;
+pub mod sanity;
+pub mod serve;
+pub mod session;
+pub mod status;
@@ -0,0 +1,241 @@
+use hs_crawler::canteen_plan;
+
+use crate::ui::config::Config;
+use crate::ui::fetch::{self, Fetched, Selection, WeekQuery};
+use crate::ui::merge;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use nix::unistd::isatty;
+
+use std::env;
+use std::fmt::Write;
+use std::io::{self, Write as _};
+
+pub fn subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    vec![
+        SubCommand::with_name("today")
+            .about("Prints the timetable and canteen plan of today, or of the next weekday in the evening."),
+        SubCommand::with_name("day")
+            .about("Prints the timetable and canteen plan of a day.")
+            .arg(Arg::with_name("date")
+                .required(true)
+                .help("YYYY-MM-DD, DD.MM.YYYY or DD.MM.")
+            ),
+        SubCommand::with_name("week")
+            .about("Prints the timetable and canteen plan of the current week."),
+    ]
+}
+
+/// Whether stdout is a terminal, i.e. the TUI makes sense.
+pub fn stdout_is_tty() -> bool {
+    isatty(1).unwrap_or(false)
+}
+
+/// Runs `today`, `day` or `week`.
+pub fn run(config: &Config, name: &str, matches: &ArgMatches) -> Result<(), String> {
+    let days = match name {
+        "day" => vec![parse_date(matches.value_of("date").unwrap_or(""))?],
+        "week" => {
+            let first = crate::initial_day(config);
+            let monday = first - chrono::Duration::days(first.weekday().num_days_from_monday() as i64);
+            (0..7).map(|i| monday + chrono::Duration::days(i)).collect()
+        }
+        _ => vec![crate::initial_day(config)],
+    };
+
+    let data = data_for(&config.courses, &days);
+
+    print_days(config, &days, data, name == "week")
+}
+
+/// Prints `days` of `data`, leaving out days without any data if `skip_empty`.
+pub fn print_days(config: &Config, days: &[Date<Local>], mut data: Fetched, skip_empty: bool) -> Result<(), String> {
+    config.filter.apply(&mut data.timetable);
+    let style = Style::detect();
+
+    let mut out = String::new();
+    for day in days.iter() {
+        let has_data = merge::lectures(&data.timetable, day).is_some() || data.canteen.contains_key(day);
+        if skip_empty && !has_data {
+            continue;
+        }
+
+        print_day(&mut out, &style, config, day, &data);
+    }
+
+    if out.is_empty() {
+        return Err("Nothing to show for that week.".to_string());
+    }
+
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(out.as_bytes()).and_then(|_| stdout.flush()) {
+        // Like `hs_app week | head`, whoever reads is done.
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(|e| e.to_string()),
+    }
+}
+
+/// Fetches the weeks `days` are in, or only reads the cache if the crawler can't.
+fn data_for(courses: &[String], days: &[Date<Local>]) -> Fetched {
+    let weeks = [WeekQuery::This, WeekQuery::Next]
+        .iter()
+        .cloned()
+        .filter(|w| days.iter().any(|d| w.contains(d)))
+        .collect::<Vec<WeekQuery>>();
+
+    if weeks.is_empty() {
+        return fetch::read_cached(courses);
+    }
+
+    let fetched = fetch::fetch_cached(
+        &Selection {
+            weeks,
+            timetable: true,
+            canteen: true,
+        },
+        courses,
+    );
+
+    for e in fetched.errors.iter() {
+        eprintln!("Warning: {}", e);
+    }
+
+    fetched
+}
+
+fn print_day(out: &mut String, style: &Style, config: &Config, day: &Date<Local>, data: &Fetched) {
+    let courses = &config.courses;
+    let diet = &config.diet;
+
+    writeln!(
+        out,
+        "{}",
+        style.bold(&format!(
+            "{} {:02}.{:02}.{}",
+            crate::german_weekday(day.weekday()),
+            day.day(),
+            day.month(),
+            day.year()
+        ))
+    ).unwrap();
+
+    let cached = courses.iter().filter_map(|c| data.cached_since(Some(c), day)).min();
+    writeln!(
+        out,
+        "  {}{}",
+        style.underline(&format!("Stundenplan {}", courses.join(", "))),
+        freshness(style, cached)
+    ).unwrap();
+    match merge::lectures(&data.timetable, day) {
+        None => writeln!(out, "    {}", style.dim("keine Daten")).unwrap(),
+        Some(lectures) => {
+            if lectures.is_empty() {
+                writeln!(out, "    {}", style.dim("frei")).unwrap();
+            }
+
+            for l in lectures {
+                let time = match (l.start, l.end) {
+                    (Some(s), Some(e)) => format!("{}-{}", s.format("%H:%M"), e.format("%H:%M")),
+                    _ => format!("{:>5}.     ", l.slot + 1),
+                };
+
+                let details = l.room.iter().chain(l.lecturer.iter()).cloned().collect::<Vec<String>>();
+                let title = merge::label(courses.len() > 1, &l.course, &l.title);
+
+                if details.is_empty() {
+                    writeln!(out, "    {}  {}", style.dim(&time), title).unwrap();
+                } else {
+                    writeln!(out, "    {}  {} {}", style.dim(&time), title, style.dim(&format!("({})", details.join(", ")))).unwrap();
+                }
+            }
+        }
+    }
+
+    writeln!(out, "  {}{}", style.underline("Mensa"), freshness(style, data.cached_since(None, day))).unwrap();
+    match data.canteen.get(day) {
+        None => writeln!(out, "    {}", style.dim("keine Daten")).unwrap(),
+        Some(items) => {
+            let meals = canteen_plan::meals(items);
+            if meals.is_empty() {
+                writeln!(out, "    {}", style.dim("geschlossen")).unwrap();
+            }
+
+            for m in diet.shown(meals) {
+                let mut description = format!("{}{}", diet.mark(&m), m.description);
+                if diet.is_preferred(&m) {
+                    description = style.bold(&description);
+                }
+
+                let conflicts = diet.conflicts(&m);
+                if !conflicts.is_empty() {
+                    description = format!("{} {}", description, style.dim(&format!("({})", conflicts.join(", "))));
+                }
+
+                match m.name {
+                    Some(ref name) => writeln!(out, "    {} {}", style.dim(&format!("{}:", name)), description).unwrap(),
+                    None => writeln!(out, "    {}", description).unwrap(),
+                }
+            }
+        }
+    }
+
+    out.push('\n');
+}
+
+/// Like " (cached, 3 days old)" for data from the cache.
+fn freshness(style: &Style, cached: Option<DateTime<Local>>) -> String {
+    match cached {
+        Some(at) => format!(" {}", style.dim(&format!("({})", fetch::cached_label(&at)))),
+        None => String::new(),
+    }
+}
+
+pub fn parse_date(input: &str) -> Result<Date<Local>, String> {
+    let input = input.trim();
+
+    let naive = NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(input, "%d.%m.%Y"))
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}{}", input, Local::today().year()), "%d.%m.%Y"))
+        .map_err(|_| format!("Can't read date '{}', use YYYY-MM-DD or DD.MM.YYYY.", input))?;
+
+    Local
+        .from_local_date(&naive)
+        .single()
+        .ok_or_else(|| format!("Invalid date '{}'.", input))
+}
+
+/// Light ANSI styling, only on terminals and without `NO_COLOR`.
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn detect() -> Self {
+        Style {
+            color: stdout_is_tty() && env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    fn wrap(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1B[{}m{}\x1B[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.wrap("1", text)
+    }
+
+    fn dim(&self, text: &str) -> String {
+        self.wrap("2", text)
+    }
+
+    fn underline(&self, text: &str) -> String {
+        self.wrap("4", text)
+    }
+}
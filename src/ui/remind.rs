@@ -0,0 +1,210 @@
+use hs_crawler::canteen_plan;
+use hs_crawler::timetable;
+
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::fetch::{self, Fetched};
+use crate::ui::filter::Filter;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use std::collections::HashSet;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the cache is checked for changes.
+const POLL_INTERVAL_SEC: u64 = 30;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("remind")
+        .about("Runs a command shortly before each lecture, until stopped.")
+        .arg(Arg::with_name("exec")
+            .short("e")
+            .long("exec")
+            .takes_value(true)
+            .required(true)
+            .help("Shell command to run, details are in HS_* environment variables.")
+        ).arg(Arg::with_name("before")
+            .short("b")
+            .long("before")
+            .takes_value(true)
+            .default_value("10")
+            .help("Minutes before the lecture.")
+        ).arg(Arg::with_name("canteen")
+            .long("canteen")
+            .takes_value(true)
+            .value_name("HH:MM")
+            .help("Also remind of the canteen plan at this time.")
+        )
+}
+
+/// Something to remind of.
+#[derive(Debug)]
+struct Reminder {
+    key: String,
+    at: DateTime<Local>,
+    env: Vec<(&'static str, String)>,
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    let courses = &config.courses;
+    let exec = matches.value_of("exec").unwrap_or_default().to_string();
+    let before: i64 = matches
+        .value_of("before")
+        .unwrap_or("10")
+        .parse()
+        .map_err(|_| "--before expects minutes.")?;
+    let canteen = match matches.value_of("canteen") {
+        Some(time) => Some(
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Can't read time '{}', use HH:MM.", time))?,
+        ),
+        None => None,
+    };
+
+    let mut fired: HashSet<String> = HashSet::new();
+    let mut loaded: Option<Vec<Option<SystemTime>>> = None;
+    let mut reminders: Vec<Reminder> = vec![];
+
+    // Nothing that is already due at start-up, only what comes after.
+    let started = Local::now();
+
+    loop {
+        let modified = courses
+            .iter()
+            .map(|course| {
+                cache::cache_path(course)
+                    .ok()
+                    .and_then(|p| p.metadata().ok())
+                    .and_then(|m| m.modified().ok())
+            })
+            .collect::<Vec<_>>();
+
+        if Some(&modified) != loaded.as_ref() {
+            let data = fetch::read_cached(courses);
+
+            for course in courses.iter().filter(|c| !data.timetable.contains_key(*c)) {
+                eprintln!("No cached data for {} yet, run hs_app once.", course);
+            }
+
+            reminders = schedule(&data, &config.filter, before, canteen);
+            let upcoming = reminders.iter().filter(|r| r.at > Local::now()).count();
+            eprintln!("Loaded {} upcoming reminders for {}.", upcoming, courses.join(", "));
+
+            loaded = Some(modified);
+        }
+
+        let now = Local::now();
+        for r in reminders.iter() {
+            if r.at <= now && r.at > started && !fired.contains(&r.key) {
+                fire(&exec, r);
+                fired.insert(r.key.clone());
+            }
+        }
+
+        let next = reminders
+            .iter()
+            .filter(|r| r.at > now)
+            .map(|r| r.at.signed_duration_since(now).num_seconds().max(1) as u64)
+            .min()
+            .unwrap_or(POLL_INTERVAL_SEC);
+
+        thread::sleep(Duration::from_secs(next.min(POLL_INTERVAL_SEC)));
+    }
+}
+
+fn fire(exec: &str, reminder: &Reminder) {
+    let result = Command::new("sh")
+        .arg("-c")
+        .arg(exec)
+        .envs(reminder.env.iter().map(|(k, v)| (*k, v.as_str())))
+        .status();
+
+    match result {
+        Ok(status) if !status.success() => eprintln!("'{}' for {} exited with {}", exec, reminder.key, status),
+        Err(e) => eprintln!("Can't run '{}': {}", exec, e),
+        Ok(_) => (),
+    }
+}
+
+fn schedule(data: &Fetched, filter: &Filter, before: i64, canteen: Option<NaiveTime>) -> Vec<Reminder> {
+    let mut reminders = vec![];
+
+    for (course, date, cells) in data
+        .timetable
+        .iter()
+        .flat_map(|(course, week)| week.iter().map(move |(date, cells)| (course, date, cells)))
+    {
+        let lectures = timetable::lectures(course, cells)
+            .into_iter()
+            .filter(|l| !filter.hides(l))
+            .collect::<Vec<_>>();
+
+        for (i, l) in lectures.iter().enumerate() {
+            // The second half of a double block needs no reminder.
+            let continued = i > 0 && lectures[i - 1].slot + 1 == l.slot && lectures[i - 1].title == l.title;
+            let (start, end) = match (l.start, l.end) {
+                (Some(start), Some(end)) if !continued => (start, end),
+                _ => continue,
+            };
+
+            let at = match date.and_time(start) {
+                Some(at) => at - chrono::Duration::minutes(before),
+                None => continue,
+            };
+
+            reminders.push(Reminder {
+                key: format!("{}-{}-{}", course, date.format("%Y%m%d"), l.slot),
+                at,
+                env: vec![
+                    ("HS_KIND", "lecture".to_string()),
+                    ("HS_COURSE", course.clone()),
+                    ("HS_DATE", date.format("%Y-%m-%d").to_string()),
+                    ("HS_START", start.format("%H:%M").to_string()),
+                    ("HS_END", end.format("%H:%M").to_string()),
+                    ("HS_TITLE", l.title.clone()),
+                    ("HS_ROOM", l.room.clone().unwrap_or_default()),
+                    ("HS_LECTURER", l.lecturer.clone().unwrap_or_default()),
+                    ("HS_TEXT", l.text.clone()),
+                ],
+            });
+        }
+    }
+
+    if let Some(time) = canteen {
+        for (date, items) in data.canteen.iter() {
+            let meals = canteen_plan::meals(items);
+            let at = match date.and_time(time) {
+                Some(at) if !meals.is_empty() => at,
+                _ => continue,
+            };
+
+            let text = meals
+                .iter()
+                .map(|m| match m.name {
+                    Some(ref name) => format!("{}: {}", name, m.description),
+                    None => m.description.clone(),
+                })
+                .collect::<Vec<String>>();
+
+            reminders.push(Reminder {
+                key: format!("{}-canteen", date.format("%Y%m%d")),
+                at,
+                env: vec![
+                    ("HS_KIND", "canteen".to_string()),
+                    ("HS_COURSE", data.timetable.keys().cloned().collect::<Vec<_>>().join(",")),
+                    ("HS_DATE", date.format("%Y-%m-%d").to_string()),
+                    ("HS_START", time.format("%H:%M").to_string()),
+                    ("HS_TITLE", "Mensa".to_string()),
+                    ("HS_TEXT", text.join("\n")),
+                ],
+            });
+        }
+    }
+
+    reminders.sort_by_key(|r| r.at);
+    reminders
+}
@@ -0,0 +1,210 @@
+use chrono::prelude::*;
+
+use std::collections::HashMap;
+
+type Week = HashMap<Date<Local>, Vec<String>>;
+
+/// More than this many rows per day means the parser picked up junk.
+const MAX_TIMETABLE_SLOTS: usize = 10;
+const MAX_MEALS_PER_DAY: usize = 12;
+
+/// Checks a freshly parsed timetable week, `previous` is what the cache had.
+/// Returns human readable warnings, empty if everything looks plausible.
+pub fn check_timetable(week: &Week, previous: &Week) -> Vec<String> {
+    let mut warnings = check_week("Timetable", week, previous);
+
+    let mut slot_counts = week.values().map(|d| d.len()).collect::<Vec<usize>>();
+    slot_counts.sort();
+    slot_counts.dedup();
+
+    match slot_counts.last() {
+        Some(&n) if n > MAX_TIMETABLE_SLOTS => warnings.push(format!(
+            "Timetable {}: {} slots per day, expected at most {}.",
+            week_label(week),
+            n,
+            MAX_TIMETABLE_SLOTS
+        )),
+        _ => (),
+    }
+
+    if slot_counts.len() > 1 {
+        warnings.push(format!(
+            "Timetable {}: days have different slot counts ({:?}).",
+            week_label(week),
+            slot_counts
+        ));
+    }
+
+    warnings
+}
+
+/// See `check_timetable`.
+pub fn check_canteen(week: &Week, previous: &Week) -> Vec<String> {
+    let mut warnings = check_week("Canteen", week, previous);
+
+    for (date, meals) in sorted(week) {
+        if meals.len() > MAX_MEALS_PER_DAY {
+            warnings.push(format!(
+                "Canteen {}: {} meals on {}, expected at most {}.",
+                week_label(week),
+                meals.len(),
+                short_date(date),
+                MAX_MEALS_PER_DAY
+            ));
+        }
+    }
+
+    warnings
+}
+
+fn check_week(source: &str, week: &Week, previous: &Week) -> Vec<String> {
+    let mut warnings = vec![];
+    let label = week_label(week);
+
+    // Mo-Fr, sometimes Sa.
+    if week.len() < 5 || week.len() > 6 {
+        warnings.push(format!("{} {}: {} days, expected 5 or 6.", source, label, week.len()));
+    }
+
+    let days = sorted(week);
+
+    if let Some((first, _)) = days.first() {
+        if first.weekday() != Weekday::Mon {
+            warnings.push(format!(
+                "{} {}: week starts on {}, expected Monday.",
+                source,
+                label,
+                crate::german_weekday(first.weekday())
+            ));
+        }
+    }
+
+    let total: usize = days.iter().map(|(_, items)| count_items(items)).sum();
+    if !week.is_empty() && total == 0 {
+        warnings.push(format!("{} {}: every day is empty.", source, label));
+    }
+
+    let previous_total: usize = days
+        .iter()
+        .filter_map(|(d, _)| previous.get(d))
+        .map(|items| count_items(items))
+        .sum();
+
+    if previous_total > 0 && total * 2 < previous_total {
+        warnings.push(format!(
+            "{} {}: {} entries, the cached version had {}.",
+            source, label, total, previous_total
+        ));
+    }
+
+    for (date, items) in days.iter() {
+        let before = previous.get(date).map(|p| count_items(p)).unwrap_or(0);
+        if before > 0 && count_items(items) == 0 && total > 0 {
+            warnings.push(format!(
+                "{} {}: {} is empty now, the cached version had {} entries.",
+                source,
+                label,
+                short_date(date),
+                before
+            ));
+        }
+    }
+
+    warnings
+}
+
+fn count_items(items: &[String]) -> usize {
+    items.iter().filter(|i| !i.trim().is_empty()).count()
+}
+
+fn sorted(week: &Week) -> Vec<(&Date<Local>, &Vec<String>)> {
+    let mut days = week.iter().collect::<Vec<_>>();
+    days.sort_by_key(|(d, _)| **d);
+    days
+}
+
+fn short_date(date: &Date<Local>) -> String {
+    format!("{} {:02}.{:02}.", &crate::german_weekday(date.weekday())[..2], date.day(), date.month())
+}
+
+fn week_label(week: &Week) -> String {
+    match week.keys().min() {
+        Some(d) => format!("week of {:02}.{:02}.", d.day(), d.month()),
+        None => "week".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monday() -> Date<Local> {
+        Local.ymd(2024, 4, 8)
+    }
+
+    /// `days` days from Monday on, each with the same entries.
+    fn week(days: i64, items: &[&str]) -> Week {
+        (0..days)
+            .map(|i| (monday() + chrono::Duration::days(i), items.iter().map(|s| s.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn plausible_weeks_have_no_warnings() {
+        let week = week(5, &["Mathematik 2", "", "Datenbanken"]);
+
+        assert!(check_timetable(&week, &Week::new()).is_empty());
+        assert!(check_timetable(&week, &week).is_empty());
+        assert!(check_canteen(&week, &Week::new()).is_empty());
+    }
+
+    #[test]
+    fn odd_day_counts_and_starts_are_reported() {
+        let short = week(3, &["Mathematik 2"]);
+        let warnings = check_timetable(&short, &Week::new());
+        assert!(warnings.iter().any(|w| w.contains("3 days")), "{:?}", warnings);
+
+        let shifted = week(6, &["Mathematik 2"])
+            .into_iter()
+            .filter(|(d, _)| d.weekday() != Weekday::Mon)
+            .collect::<Week>();
+        let warnings = check_timetable(&shifted, &Week::new());
+        assert!(warnings.iter().any(|w| w.contains("expected Monday")), "{:?}", warnings);
+    }
+
+    #[test]
+    fn empty_weeks_are_reported() {
+        let warnings = check_canteen(&week(5, &["", " "]), &Week::new());
+        assert!(warnings.iter().any(|w| w.contains("every day is empty")), "{:?}", warnings);
+    }
+
+    #[test]
+    fn too_many_rows_are_reported() {
+        let mut week = week(5, &["Mathematik 2"]);
+        week.insert(monday(), vec!["Essen".to_string(); MAX_MEALS_PER_DAY + 1]);
+
+        let timetable = check_timetable(&week, &Week::new());
+        assert!(timetable.iter().any(|w| w.contains("slots per day")), "{:?}", timetable);
+        assert!(timetable.iter().any(|w| w.contains("different slot counts")), "{:?}", timetable);
+
+        let canteen = check_canteen(&week, &Week::new());
+        assert!(canteen.iter().any(|w| w.contains("meals on Mo 08.04.")), "{:?}", canteen);
+    }
+
+    #[test]
+    fn shrinking_against_the_cache_is_reported() {
+        let previous = week(5, &["Mathematik 2", "Datenbanken"]);
+
+        let mut now = previous.clone();
+        now.insert(monday(), vec![String::new(); 2]);
+        let warnings = check_timetable(&now, &previous);
+        assert!(warnings.iter().any(|w| w.contains("Mo 08.04. is empty now")), "{:?}", warnings);
+
+        let mostly_gone = week(5, &["", "Datenbanken"])
+            .into_iter()
+            .map(|(d, items)| if d == monday() { (d, items) } else { (d, vec![String::new(); 2]) })
+            .collect::<Week>();
+        let warnings = check_timetable(&mostly_gone, &previous);
+        assert!(warnings.iter().any(|w| w.contains("the cached version had 10")), "{:?}", warnings);
+    }
+}
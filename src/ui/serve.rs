@@ -0,0 +1,169 @@
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::export;
+use crate::ui::filter::Filter;
+use crate::ui::fetch::{self, Fetched, Selection};
+use crate::ui::http::{self, Request, Response};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ICS_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Calendar clients poll at least daily, a feed nobody asked for in a week is dropped.
+const FEED_EXPIRY_SEC: u64 = 7 * 24 * 60 * 60;
+
+/// At most this many courses are refreshed in the background.
+const MAX_FEEDS: usize = 32;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("serve")
+        .about("Serves the timetable and canteen plan as calendar feeds.")
+        .arg(Arg::with_name("bind")
+            .short("b")
+            .long("bind")
+            .takes_value(true)
+            .default_value("127.0.0.1:8642")
+            .help("Address to listen on, use 0.0.0.0:PORT for the LAN.")
+        ).arg(Arg::with_name("refresh")
+            .long("refresh")
+            .takes_value(true)
+            .help("Minutes between background refreshes, refresh_minutes of the config by default.")
+        )
+}
+
+/// Courses that were asked for recently, refreshed in the background.
+struct Feeds {
+    default_course: String,
+    filter: Filter,
+    /// When each course was last asked for.
+    courses: Mutex<BTreeMap<String, Instant>>,
+}
+
+impl Feeds {
+    /// Makes way for `course` by dropping the one asked for longest ago, if there are too many.
+    fn requested(&self, course: &str) {
+        let mut courses = self.courses.lock().unwrap();
+        courses.insert(course.to_string(), Instant::now());
+
+        if courses.len() > MAX_FEEDS {
+            let oldest = courses
+                .iter()
+                .filter(|(c, _)| **c != self.default_course)
+                .min_by_key(|(_, at)| **at)
+                .map(|(c, _)| c.clone());
+
+            if let Some(oldest) = oldest {
+                courses.remove(&oldest);
+            }
+        }
+    }
+
+    /// The courses to refresh, the default one and those asked for recently.
+    fn active(&self) -> Vec<String> {
+        let mut courses = self.courses.lock().unwrap();
+        let expiry = Duration::from_secs(FEED_EXPIRY_SEC);
+
+        courses.retain(|c, at| *c == self.default_course || at.elapsed() < expiry);
+        courses.keys().cloned().collect()
+    }
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    let course = config.course();
+    let bind = matches.value_of("bind").unwrap_or("127.0.0.1:8642").to_string();
+    let refresh: u64 = match matches.value_of("refresh") {
+        Some(minutes) => minutes.parse().map_err(|_| "--refresh expects minutes.")?,
+        None => config.refresh_minutes,
+    };
+
+    let feeds = Arc::new(Feeds {
+        default_course: course.to_string(),
+        filter: config.filter.clone(),
+        courses: Mutex::new(vec![(course.to_string(), Instant::now())].into_iter().collect()),
+    });
+
+    let refresher = feeds.clone();
+    thread::spawn(move || loop {
+        for course in refresher.active() {
+            refresh_course(&course);
+        }
+
+        thread::sleep(Duration::from_secs(refresh.max(1) * 60));
+    });
+
+    eprintln!("Serving on http://{}/calendar/{}.ics and http://{}/canteen.ics", bind, course, bind);
+
+    http::serve(&bind, move |req| handle(&feeds, req))
+}
+
+fn refresh_course(course: &str) -> Fetched {
+    let fetched = fetch::fetch_cached(&Selection::default(), &[course.to_string()]);
+
+    for e in fetched.errors.iter() {
+        eprintln!("Refreshing {}: {}", course, e);
+    }
+
+    fetched
+}
+
+fn handle(feeds: &Feeds, req: &Request) -> Response {
+    if req.path == "/canteen.ics" {
+        let data = cached_or_fetched(&feeds.default_course);
+
+        return match data {
+            Ok(data) if !data.canteen.is_empty() => {
+                Response::new(200, ICS_CONTENT_TYPE, export::canteen_ics(&data))
+            }
+            Ok(_) => Response::text(503, "No canteen plan yet."),
+            Err(e) => Response::text(502, &e),
+        };
+    }
+
+    let course = req
+        .path
+        .strip_prefix("/calendar/")
+        .and_then(|rest| rest.strip_suffix(".ics"))
+        .map(|c| c.to_uppercase());
+
+    match course {
+        Some(ref course) if fetch::is_valid_course(course) => {
+            match cached_or_fetched(course) {
+                Ok(mut data) if !data.timetable.is_empty() => {
+                    feeds.filter.apply(&mut data.timetable);
+                    feeds.requested(course);
+                    Response::new(200, ICS_CONTENT_TYPE, export::timetable_ics(&data))
+                }
+                Ok(_) => Response::text(404, &format!("No timetable for {}.", course)),
+                Err(e) => Response::text(502, &e),
+            }
+        }
+        Some(_) => Response::text(400, "Invalid course name."),
+        None => Response::text(404, "Try /calendar/<course>.ics or /canteen.ics."),
+    }
+}
+
+/// The cached data of `course`, fetched first if there is no cached timetable yet.
+fn cached_or_fetched(course: &str) -> Result<Fetched, String> {
+    if let Ok(Some(data)) = cache::read_cache(course) {
+        if !data.timetable.is_empty() {
+            return Ok(Fetched::from_cache(course, data));
+        }
+    }
+
+    let fetched = refresh_course(course);
+
+    if fetched.timetable.is_empty() && fetched.canteen.is_empty() {
+        return Err(fetched
+            .errors
+            .first()
+            .map(|e| e.message.clone())
+            .unwrap_or_else(|| "Nothing fetched.".to_string()));
+    }
+
+    Ok(fetched)
+}
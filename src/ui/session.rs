@@ -0,0 +1,69 @@
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::fetch;
+
+use chrono::prelude::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+/// What the TUI looked like when it was left, restored on the next start.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Session {
+    pub display_mode: usize,
+    pub courses: Vec<String>,
+    pub canteen: String,
+    /// Only restored with `restore_day`, together with `selected` and `scroll`.
+    pub day: Option<NaiveDate>,
+    pub selected: usize,
+    /// Horizontal and vertical offset of the view.
+    pub scroll: (isize, isize),
+    /// Rules added with the hide key, so unhide still works after a restart.
+    pub hidden: Vec<String>,
+}
+
+/// Next to the cache, a lost session costs nothing.
+fn session_path() -> Result<PathBuf, String> {
+    Ok(cache::cache_dir()?.join("session.json"))
+}
+
+/// The last session, or a blank one if there is none or it can't be read.
+pub fn load() -> Session {
+    session_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(session: &Session) -> Result<(), String> {
+    let out = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    cache::write_atomic(&session_path()?, &out)
+}
+
+impl Session {
+    /// Takes over the courses and canteen of the last session where neither
+    /// the command line nor the config file chose them.
+    pub fn restore_sources(&self, config: &mut Config, courses_given: bool, canteen_given: bool) {
+        let valid = !self.courses.is_empty() && self.courses.iter().all(|c| fetch::is_valid_course(c));
+        if !courses_given && valid {
+            config.courses = self.courses.clone();
+        }
+
+        if !canteen_given && !self.canteen.trim().is_empty() {
+            config.canteen = self.canteen.clone();
+        }
+    }
+
+    /// The day to show instead of `initial_day`, if it isn't in the past.
+    pub fn day(&self, config: &Config) -> Option<Date<Local>> {
+        if !config.restore_day {
+            return None;
+        }
+
+        self.day
+            .and_then(|d| Local.from_local_date(&d).single())
+            .filter(|d| *d >= Local::today())
+    }
+}
@@ -0,0 +1,274 @@
+use hs_crawler::canteen_plan;
+use hs_crawler::timetable::Lecture;
+
+use crate::ui::cache;
+use crate::ui::config::Config;
+use crate::ui::fetch::{self, Fetched, Selection};
+use crate::ui::merge;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use chrono::prelude::*;
+
+use std::env;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How far ahead to look for the next lecture.
+const LOOKAHEAD_DAYS: i64 = 7;
+
+const MEAL_MAX_CHARS: usize = 32;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("status")
+        .about("Prints the next lecture and today's canteen highlight for status bars, from the cache only.")
+        .arg(Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["plain", "tmux", "i3bar", "waybar"])
+            .default_value("plain")
+            .help("Output format.")
+        ).arg(Arg::with_name("refresh")
+            .long("refresh")
+            .takes_value(true)
+            .value_name("MINUTES")
+            .help("Refreshes the cache in the background if it is older than this.")
+        ).arg(Arg::with_name("refresh-only")
+            .long("refresh-only")
+            .hidden(true)
+        )
+}
+
+#[derive(Serialize)]
+struct I3Block {
+    name: &'static str,
+    full_text: String,
+    short_text: String,
+    urgent: bool,
+}
+
+#[derive(Serialize)]
+struct WaybarModule {
+    text: String,
+    tooltip: String,
+    class: &'static str,
+}
+
+/// What the status line says, independent of the output format.
+struct Status {
+    next: Option<String>,
+    meal: Option<String>,
+    tooltip: String,
+    class: &'static str,
+}
+
+pub fn run(config: &Config, matches: &ArgMatches) -> Result<(), String> {
+    if matches.is_present("refresh-only") {
+        fetch::fetch_cached(&Selection::default(), &config.courses);
+        return Ok(());
+    }
+
+    if let Some(minutes) = matches.value_of("refresh") {
+        let minutes: u64 = minutes.parse().map_err(|_| "--refresh expects minutes.")?;
+        let stale = config.courses.iter().any(|course| {
+            cache::cache_age(course)
+                .map(|age| age > Duration::from_secs(minutes * 60))
+                .unwrap_or(true)
+        });
+
+        if stale {
+            refresh_in_background(matches.value_of("config"), &config.courses);
+        }
+    }
+
+    let fetched = fetch::read_cached(&config.courses);
+    let status = if fetched.timetable.is_empty() && fetched.canteen.is_empty() {
+        Status {
+            next: None,
+            meal: None,
+            tooltip: format!("No cached data for {}, run hs_app once.", config.courses.join(", ")),
+            class: "stale",
+        }
+    } else {
+        status(&fetched, config, Local::now())
+    };
+
+    let out = match matches.value_of("format").unwrap_or("plain") {
+        "tmux" => tmux(&status),
+        "i3bar" => serde_json::to_string(&I3Block {
+            name: "hs_app",
+            full_text: plain(&status),
+            short_text: status.next.clone().unwrap_or_default(),
+            urgent: status.class == "soon",
+        }).map_err(|e| e.to_string())?,
+        "waybar" => serde_json::to_string(&WaybarModule {
+            text: plain(&status),
+            tooltip: status.tooltip.clone(),
+            class: status.class,
+        }).map_err(|e| e.to_string())?,
+        _ => plain(&status),
+    };
+
+    println!("{}", out);
+    Ok(())
+}
+
+/// Starts `hs_app status --refresh-only` detached, so the bar isn't blocked by the network.
+/// It gets the same config file and courses, so it refreshes what is shown.
+fn refresh_in_background(config_path: Option<&str>, courses: &[String]) {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(_) => return,
+    };
+
+    let mut command = Command::new(exe);
+    command.args(["status", "--refresh-only"]);
+
+    if let Some(path) = config_path {
+        command.args(["--config", path]);
+    }
+
+    for course in courses {
+        command.args(["--course", course]);
+    }
+
+    let _ = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+fn status(data: &Fetched, config: &Config, now: DateTime<Local>) -> Status {
+    let filter = &config.filter;
+    let diet = &config.diet;
+    let today = now.date();
+    let mut next = None;
+    let mut class = "free";
+
+    for offset in 0..LOOKAHEAD_DAYS {
+        let day = today + chrono::Duration::days(offset);
+        let lectures = match merge::lectures(&data.timetable, &day) {
+            Some(lectures) => lectures,
+            None => continue,
+        };
+
+        let found = lectures.into_iter().filter(|l| !filter.hides(l)).find(|l| match l.end {
+            Some(end) => offset > 0 || end > now.time(),
+            None => false,
+        });
+
+        if let Some(l) = found {
+            let (text, c) = describe(&l, &day, now);
+            next = Some(text);
+            class = c;
+            break;
+        }
+    }
+
+    let meals = data
+        .canteen
+        .get(&today)
+        .map(|items| diet.shown(canteen_plan::meals(items)))
+        .unwrap_or_default();
+
+    let meal = diet
+        .best_pick(&meals)
+        .or_else(|| meals.iter().find(|m| !diet.is_avoided(m)))
+        .map(|m| shorten(&m.description, MEAL_MAX_CHARS));
+
+    let mut tooltip = merge::lectures(&data.timetable, &today)
+        .unwrap_or_default()
+        .iter()
+        .filter(|l| !filter.hides(l))
+        .map(|l| {
+            let text = merge::label(data.timetable.len() > 1, &l.course, &title_and_room(l));
+            match (l.start, l.end) {
+                (Some(s), Some(e)) => format!("{}-{} {}", s.format("%H:%M"), e.format("%H:%M"), text),
+                _ => text,
+            }
+        })
+        .collect::<Vec<String>>();
+
+    tooltip.extend(meals.iter().map(|m| match m.name {
+        Some(ref name) => format!("{}: {}{}", name, diet.mark(m), m.description),
+        None => format!("{}{}", diet.mark(m), m.description),
+    }));
+
+    Status {
+        next,
+        meal,
+        tooltip: tooltip.join("\n"),
+        class,
+    }
+}
+
+fn describe(l: &Lecture, day: &Date<Local>, now: DateTime<Local>) -> (String, &'static str) {
+    let start = match l.start {
+        Some(start) => start,
+        None => return (format!("Next: {}", title_and_room(l)), "later"),
+    };
+
+    if *day != now.date() {
+        let weekday = &crate::german_weekday(day.weekday())[..2];
+        return (format!("Next: {} {} {}", title_and_room(l), weekday, start.format("%H:%M")), "later");
+    }
+
+    if start <= now.time() {
+        let end = l.end.map(|e| e.format("%H:%M").to_string()).unwrap_or_default();
+        return (format!("Now: {} until {}", title_and_room(l), end), "running");
+    }
+
+    let minutes = start.signed_duration_since(now.time()).num_minutes();
+    if minutes < 60 {
+        (format!("Next: {} in {} min", title_and_room(l), minutes), "soon")
+    } else {
+        (format!("Next: {} at {}", title_and_room(l), start.format("%H:%M")), "later")
+    }
+}
+
+fn title_and_room(l: &Lecture) -> String {
+    match l.room {
+        Some(ref room) => format!("{} @ {}", l.title, room),
+        None => l.title.clone(),
+    }
+}
+
+fn shorten(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let mut short = text.chars().take(max - 1).collect::<String>();
+    short.push('…');
+    short
+}
+
+fn plain(status: &Status) -> String {
+    let parts = status
+        .next
+        .iter()
+        .cloned()
+        .chain(status.meal.iter().map(|m| format!("Mensa: {}", m)))
+        .collect::<Vec<String>>();
+
+    if parts.is_empty() {
+        return match status.class {
+            "stale" => "hs_app: no data".to_string(),
+            _ => "No lectures".to_string(),
+        };
+    }
+
+    parts.join(" | ")
+}
+
+fn tmux(status: &Status) -> String {
+    let text = plain(status).replace('#', "##");
+
+    match status.class {
+        "soon" | "running" => format!("#[bold]{}#[nobold]", text),
+        "stale" => format!("#[dim]{}#[nodim]", text),
+        _ => text,
+    }
+}
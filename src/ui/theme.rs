@@ -20,6 +20,19 @@ pub fn select_colorscheme() -> Theme {
         .map(|s| s.to_lowercase().contains("truecolor"))
         .unwrap_or(false);
 
+    colorscheme(truecolor)
+}
+
+/// The theme by name as in the config, "auto" sniffs `COLORTERM`.
+pub fn colorscheme_by_name(name: &str) -> Theme {
+    match name {
+        "truecolor" => colorscheme(true),
+        "simple" => colorscheme(false),
+        _ => select_colorscheme(),
+    }
+}
+
+pub fn colorscheme(truecolor: bool) -> Theme {
     if truecolor {
         Theme {
             background: solarized::CYAN,